@@ -1 +1,468 @@
+use chrono::NaiveDate;
 
+use super::habit::{parse_goal, HabitType};
+use super::keymap::Action;
+use super::{Order, View};
+
+/// Command - A command typed in the command bar, after `:`
+/// Habits are referred to by their ID or name, and looked up when the command is run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Add {
+        label: String,
+        habit_type: HabitType,
+        schedule: Option<String>,
+        goal: Option<f64>,
+        unit: Option<String>,
+        group: Option<String>,
+    },
+    Edit {
+        habit: String,
+        label: String,
+    },
+    Goal {
+        habit: String,
+        goal: f64,
+        unit: Option<String>,
+    },
+    Schedule {
+        habit: String,
+        rule: String,
+    },
+    Delete {
+        habit: String,
+    },
+    /// Hide a habit from the grid but keep its stats, the selected one if none is given
+    Archive {
+        habit: Option<String>,
+    },
+    Unarchive {
+        habit: Option<String>,
+    },
+    /// Put a habit in a group, or take it out of its group when there is none
+    Group {
+        habit: String,
+        group: Option<String>,
+    },
+    /// Fold a group or unfold it, the group of the selected habit if none is given
+    Fold {
+        group: Option<String>,
+    },
+    Sort(Order),
+    Skip,
+    Note {
+        text: String,
+    },
+    Restore {
+        index: usize,
+    },
+    View(View),
+    /// Show the days around a date and select it
+    Goto(NaiveDate),
+    /// Let past days be changed whatever the editing policy, or stop
+    Backfill,
+    Undo,
+    Redo,
+    /// Bind a key sequence to an action, or show the bindings when there is none
+    Map {
+        binding: Option<(String, Action)>,
+    },
+}
+
+/// Token - A word of a command, with the quotes and escapes taken out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Quoted words are never flags
+    pub quoted: bool,
+    /// Byte offset of the word in the input
+    pub start: usize,
+}
+
+/// Split a command into words
+/// Words are separated by whitespace, unless it is quoted with '…' or "…" or escaped with `\`
+/// Quotes only open at the start of a word, so `note didn't sleep` needs none
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut text = String::new();
+        let mut quote = None;
+        let mut quoted = false;
+        if c == '\'' || c == '"' {
+            quote = Some(c);
+            quoted = true;
+            chars.next();
+        }
+        loop {
+            match chars.next() {
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, escaped)) => text.push(escaped),
+                    None => return Err(String::from("nothing to escape after `\\`")),
+                },
+                Some((_, c)) if Some(c) == quote => quote = None,
+                Some((_, c)) if quote.is_none() && c.is_whitespace() => break,
+                Some((_, c)) => text.push(c),
+                None if quote.is_some() => {
+                    return Err(format!("unclosed quote at column {}", start + 1))
+                }
+                None => break,
+            }
+        }
+        tokens.push(Token {
+            text,
+            quoted,
+            start,
+        });
+    }
+    Ok(tokens)
+}
+
+/// The positional words and the `--flag value` or `--flag=value` pairs of a command
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Split the words, only accepting the flags the command takes
+    fn new(tokens: &[Token], command: &str, allowed: &[&str]) -> Result<Args, String> {
+        let mut positional = vec![];
+        let mut flags: Vec<(String, String)> = vec![];
+        let mut iter = tokens.iter();
+        while let Some(token) = iter.next() {
+            let name = match token.text.strip_prefix("--") {
+                Some(name) if !token.quoted && !name.is_empty() => name,
+                _ => {
+                    positional.push(token.text.to_owned());
+                    continue;
+                }
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name, value.to_owned()),
+                None => match iter.next() {
+                    Some(value) => (name, value.text.to_owned()),
+                    None => return Err(format!("missing value for `--{}`", name)),
+                },
+            };
+            if !allowed.contains(&name) {
+                return Err(match allowed {
+                    [] => format!("`{}` takes no flags, got `--{}`", command, name),
+                    _ => format!(
+                        "unknown flag `--{}` for `{}`, expected --{}",
+                        name,
+                        command,
+                        allowed.join(", --")
+                    ),
+                });
+            }
+            if flags.iter().any(|(f, _)| f == name) {
+                return Err(format!("`--{}` is given twice", name));
+            }
+            flags.push((name.to_owned(), value));
+        }
+        Ok(Args { positional, flags })
+    }
+
+    fn flag(&self, name: &str) -> Option<String> {
+        self.flags
+            .iter()
+            .find(|(f, _)| f == name)
+            .map(|(_, v)| v.to_owned())
+    }
+}
+
+/// Parse a command typed in the command bar
+pub fn parse(input: &str) -> Result<Command, String> {
+    let tokens = tokenize(input)?;
+    let (name, rest) = match tokens.split_first() {
+        Some((name, rest)) => (name.text.as_str(), rest),
+        None => return Err(String::from("no command given")),
+    };
+    let args = Args::new(rest, name, flags(name))?;
+    let positional: Vec<&str> = args.positional.iter().map(|s| s.as_str()).collect();
+
+    let command = match name {
+        // example: `add Read`, `add "Read books" count` or `add Run --type number --goal 5 --unit km`
+        "add" => {
+            let usage = "usage: `add NAME [--type TYPE] [--schedule SCHEDULE] [--goal GOAL] \
+                [--unit UNIT] [--group GROUP]`";
+            let (label, type_arg) = match positional[..] {
+                [label] => (label, None),
+                [label, habit_type] => (label, Some(habit_type)),
+                [] => return Err(format!("missing the habit name, {}", usage)),
+                _ => return Err(format!("quote names with spaces, {}", usage)),
+            };
+            let habit_type = match (type_arg, args.flag("type")) {
+                (Some(_), Some(_)) => return Err(String::from("the type is given twice")),
+                (Some(t), None) => positional_type(t)?,
+                (None, Some(t)) => HabitType::from_name(&t).ok_or_else(|| unknown_type(&t))?,
+                (None, None) => HabitType::BIT,
+            };
+            Command::Add {
+                label: label.to_owned(),
+                habit_type,
+                schedule: args.flag("schedule"),
+                goal: args.flag("goal").map(|g| parse_goal(&g)).transpose()?,
+                unit: args.flag("unit"),
+                group: args.flag("group"),
+            }
+        }
+        // example: `edit read "Read books"`
+        "edit" => match positional[..] {
+            [habit, label] => Command::Edit {
+                habit: habit.to_owned(),
+                label: label.to_owned(),
+            },
+            _ => return Err(String::from("usage: `edit HABIT NEW_NAME`")),
+        },
+        // example: `goal water 8 glasses` or `goal Water 8 --unit glasses`, a goal of 0 removes it
+        "goal" => {
+            let (habit, goal, unit) = match positional[..] {
+                [habit, goal] => (habit, goal, args.flag("unit")),
+                [habit, goal, unit] if args.flag("unit").is_none() => {
+                    (habit, goal, Some(unit.to_owned()))
+                }
+                _ => return Err(String::from("usage: `goal HABIT GOAL [UNIT]`")),
+            };
+            Command::Goal {
+                habit: habit.to_owned(),
+                goal: parse_goal(goal)?,
+                unit,
+            }
+        }
+        // example: `schedule run mon,wed,fri`
+        "schedule" => match positional[..] {
+            [habit, rule] => Command::Schedule {
+                habit: habit.to_owned(),
+                rule: rule.to_owned(),
+            },
+            _ => return Err(String::from("usage: `schedule HABIT SCHEDULE`")),
+        },
+        // example: `delete read` or `delete "Read books"`
+        "delete" => match positional[..] {
+            [habit] => Command::Delete {
+                habit: habit.to_owned(),
+            },
+            _ => return Err(String::from("usage: `delete HABIT`")),
+        },
+        // example: `archive Read` or `unarchive` for the selected habit
+        "archive" | "unarchive" => {
+            let habit = match positional[..] {
+                [] => None,
+                [habit] => Some(habit.to_owned()),
+                _ => return Err(format!("usage: `{} [HABIT]`", name)),
+            };
+            match name {
+                "archive" => Command::Archive { habit },
+                _ => Command::Unarchive { habit },
+            }
+        }
+        // example: `group Read Morning` or `group Read` to take it out of its group
+        "group" => match positional[..] {
+            [habit] => Command::Group {
+                habit: habit.to_owned(),
+                group: None,
+            },
+            [habit, group] => Command::Group {
+                habit: habit.to_owned(),
+                group: Some(group.to_owned()),
+            },
+            _ => return Err(String::from("usage: `group HABIT [GROUP]`")),
+        },
+        // example: `fold Morning` or `fold`
+        "fold" => match positional[..] {
+            [] => Command::Fold { group: None },
+            [group] => Command::Fold {
+                group: Some(group.to_owned()),
+            },
+            _ => return Err(String::from("usage: `fold [GROUP]`")),
+        },
+        // example: `sort streak`, or `sort manual` for the order they were added or moved in
+        "sort" => match positional[..] {
+            ["manual"] => Command::Sort(Order::Manual),
+            ["name"] => Command::Sort(Order::Name),
+            ["streak"] => Command::Sort(Order::Streak),
+            ["rate"] => Command::Sort(Order::Rate),
+            _ => return Err(String::from("usage: `sort manual|name|streak|rate`")),
+        },
+        // Skip the selected habit for the selected day
+        "skip" => no_args(Command::Skip, &positional, "skip")?,
+        // Leave a note on the selected day, the words don't need quotes
+        // example: `note slept in`
+        "note" => match positional.join(" ") {
+            text if text.trim().is_empty() => return Err(String::from("usage: `note TEXT`")),
+            text => Command::Note { text },
+        },
+        // example: `restore` or `restore 2`, 0 being the latest backup
+        "restore" => match positional[..] {
+            [] => Command::Restore { index: 0 },
+            [index] => Command::Restore {
+                index: index
+                    .parse()
+                    .map_err(|_| format!("invalid backup number `{}`", index))?,
+            },
+            _ => return Err(String::from("usage: `restore [BACKUP_NUMBER]`")),
+        },
+        // example: `view week`, `view month`, `view all` for the month of all habits, `view year`
+        // or `view archive`
+        "view" => match positional[..] {
+            ["week"] => Command::View(View::Week),
+            ["month"] => Command::View(View::Month { all: false }),
+            ["all"] => Command::View(View::Month { all: true }),
+            ["year"] => Command::View(View::Year),
+            ["archive"] => Command::View(View::Archive),
+            _ => return Err(String::from("usage: `view week|month|all|year|archive`")),
+        },
+        // example: `goto 2026-03-14`
+        "goto" => match positional[..] {
+            [date] => Command::Goto(
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid date `{}`, use YYYY-MM-DD", date))?,
+            ),
+            _ => return Err(String::from("usage: `goto YYYY-MM-DD`")),
+        },
+        // Change past days out of reach of the editing policy, until `backfill` again
+        "backfill" => no_args(Command::Backfill, &positional, "backfill")?,
+        "undo" => no_args(Command::Undo, &positional, "undo")?,
+        "redo" => no_args(Command::Redo, &positional, "redo")?,
+        // example: `map <C-n> down` or `map`
+        "map" => match positional[..] {
+            [] => Command::Map { binding: None },
+            [sequence, action] => Command::Map {
+                binding: Some((
+                    sequence.to_owned(),
+                    Action::from_name(action)
+                        .ok_or_else(|| format!("unknown action `{}`", action))?,
+                )),
+            },
+            _ => return Err(String::from("usage: `map KEYS ACTION`")),
+        },
+        other => {
+            return Err(format!(
+                "unknown command `{}`, expected one of {}",
+                other,
+                NAMES.join(", ")
+            ))
+        }
+    };
+    Ok(command)
+}
+
+/// The flags a command takes, without the `--`
+pub fn flags(command: &str) -> &'static [&'static str] {
+    match command {
+        "add" => &["type", "schedule", "goal", "unit", "group"],
+        "goal" => &["unit"],
+        _ => &[],
+    }
+}
+
+/// The words that can be typed after the words before them, to complete the one being typed
+/// Habits can be referred to by their ID or by one of the names
+pub fn completions(before: &[Token], word: &str, habits: &[String]) -> Vec<String> {
+    let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+    let (name, rest) = match before.split_first() {
+        Some((name, rest)) => (name.text.as_str(), rest),
+        None => return words(&NAMES),
+    };
+    if word.starts_with("--") {
+        return flags(name).iter().map(|f| format!("--{}", f)).collect();
+    }
+    // The value of a flag
+    if let Some(flag) = rest.last().and_then(|t| flag_name(t)) {
+        return match flag {
+            "type" => words(&TYPES),
+            _ => vec![],
+        };
+    }
+    // Count the positional words, leaving out the flags and their values
+    let mut position = 0;
+    let mut iter = rest.iter();
+    while let Some(token) = iter.next() {
+        match flag_name(token) {
+            Some(_) => {
+                iter.next();
+            }
+            None if !token.quoted && token.text.starts_with("--") => {}
+            None => position += 1,
+        }
+    }
+    match (name, position) {
+        ("edit" | "goal" | "schedule" | "delete" | "archive" | "unarchive" | "group", 0) => {
+            habits.to_vec()
+        }
+        ("add", 1) => words(&TYPES),
+        ("schedule", 1) => words(&["daily"]),
+        ("view", 0) => words(&["week", "month", "all", "year", "archive"]),
+        ("sort", 0) => words(&["manual", "name", "streak", "rate"]),
+        ("map", 1) => Action::ALL.iter().map(|a| a.name().to_owned()).collect(),
+        _ => vec![],
+    }
+}
+
+/// The name of a flag that takes the next word as its value
+fn flag_name(token: &Token) -> Option<&str> {
+    match token.text.strip_prefix("--") {
+        Some(name) if !token.quoted && !name.is_empty() && !name.contains('=') => Some(name),
+        _ => None,
+    }
+}
+
+const TYPES: [&str; 4] = ["bit", "count", "alpha", "number"];
+
+/// Names of all commands
+pub const NAMES: [&str; 19] = [
+    "add",
+    "edit",
+    "goal",
+    "schedule",
+    "delete",
+    "archive",
+    "unarchive",
+    "group",
+    "fold",
+    "sort",
+    "skip",
+    "note",
+    "restore",
+    "view",
+    "goto",
+    "backfill",
+    "undo",
+    "redo",
+    "map",
+];
+
+fn no_args(command: Command, positional: &[&str], name: &str) -> Result<Command, String> {
+    match positional {
+        [] => Ok(command),
+        _ => Err(format!("`{}` takes no arguments", name)),
+    }
+}
+
+/// The type given after the name, like `add Read count`
+/// Like before types had names, a number stands for COUNT and a single character for ALPHA
+fn positional_type(name: &str) -> Result<HabitType, String> {
+    if let Some(habit_type) = HabitType::from_name(name) {
+        return Ok(habit_type);
+    }
+    if name.parse::<i32>().is_ok() {
+        return Ok(HabitType::COUNT);
+    }
+    if name.chars().count() == 1 {
+        return Ok(HabitType::ALPHA);
+    }
+    Err(unknown_type(name))
+}
+
+fn unknown_type(name: &str) -> String {
+    format!(
+        "unknown habit type `{}`, expected bit, count, alpha or number",
+        name
+    )
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{Local, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use eyre::{eyre, Result};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use tui::style::Color;
+
+use super::habit::{Entry, HabitType};
+use super::keymap::{KeyMap, Sequences};
+
+const FILE_NAME: &str = "config.toml";
+
+/// Config - User settings read from `config.toml`, next to the habits
+/// Every setting is optional
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// IANA name of the timezone days are counted in, like "Europe/Berlin"
+    /// When not set, days follow the system timezone, so they move along when travelling
+    pub timezone: Option<Tz>,
+    pub grid: Grid,
+    /// Which days can be changed: "today", "free", or a number of days before today
+    #[serde(deserialize_with = "editing")]
+    pub editing: Editing,
+    pub theme: Theme,
+    pub glyphs: Glyphs,
+    /// Key sequences bound to the actions of NORMAL mode, by the name of the action
+    /// Actions that are left out keep their default keys
+    pub keys: BTreeMap<String, Sequences>,
+}
+
+impl Config {
+    /// Load the config file of the directory, the defaults if there is none
+    pub fn load(dir: &Path) -> Result<Config> {
+        let path = path(dir);
+        let config: Config = match fs::read_to_string(&path) {
+            Ok(data) => toml::from_str(&data)
+                .map_err(|e| eyre!("Invalid config in {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(eyre!("Could not read {}: {}", path.display(), e)),
+        };
+        config.keymap()?;
+        Ok(config)
+    }
+
+    /// The key map with the keys of the config
+    pub fn keymap(&self) -> Result<KeyMap> {
+        KeyMap::new(&self.keys).map_err(|e| eyre!("Invalid keys in the config: {}", e))
+    }
+}
+
+/// Path of the config file in the directory
+pub fn path(dir: &Path) -> PathBuf {
+    dir.join(FILE_NAME)
+}
+
+/// When the config file was last changed, None if there is none
+pub fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(path(dir)).ok()?.modified().ok()
+}
+
+/// The current calendar day in the timezone, or in the system timezone if there is none
+pub fn today(timezone: Option<Tz>) -> NaiveDate {
+    match timezone {
+        Some(tz) => Utc::now().with_timezone(&tz).naive_local().date(),
+        None => Local::now().naive_local().date(),
+    }
+}
+
+/// Most days the grid can show
+pub const MAX_DAYS: usize = 31;
+
+/// Grid - Which days the grid shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Grid {
+    /// Number of days shown, 7 for whole weeks, any other number for that many days up to today
+    #[serde(deserialize_with = "days")]
+    pub days: usize,
+    /// The day whole weeks start on, in the grid, the calendars, weekly quotas and totals
+    #[serde(deserialize_with = "weekday")]
+    pub week_start: Weekday,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid {
+            days: 7,
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+fn days<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let days = usize::deserialize(deserializer)?;
+    match days {
+        1..=MAX_DAYS => Ok(days),
+        _ => Err(de::Error::custom(format!(
+            "expected between 1 and {} days, got {}",
+            MAX_DAYS, days
+        ))),
+    }
+}
+
+fn weekday<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Weekday, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse()
+        .map_err(|_| de::Error::custom(format!("unknown weekday `{}`", name)))
+}
+
+/// Editing - Which days can be changed from the grid
+/// Past days out of reach can still be changed after `:backfill`, days to come never can
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Editing {
+    #[default]
+    Today,
+    /// Today and this many days before it
+    Past(u32),
+    Free,
+}
+
+impl Editing {
+    /// Whether the day can be changed on the day `today`, with the reason why not
+    pub fn check(&self, day: NaiveDate, today: NaiveDate) -> Result<(), String> {
+        if day > today {
+            return Err(format!("{} is yet to come", day));
+        }
+        let days_back = (today - day).num_days();
+        match *self {
+            Editing::Free => Ok(()),
+            Editing::Today if days_back == 0 => Ok(()),
+            Editing::Past(days) if days_back <= days as i64 => Ok(()),
+            Editing::Today => Err(format!(
+                "only today can be changed, `:backfill` to change {}",
+                day
+            )),
+            Editing::Past(days) => Err(format!(
+                "only the last {} days can be changed, `:backfill` to change {}",
+                days, day
+            )),
+        }
+    }
+}
+
+fn editing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Editing, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Policy {
+        Name(String),
+        Days(u32),
+    }
+    match Policy::deserialize(deserializer)? {
+        Policy::Name(name) if name == "today" => Ok(Editing::Today),
+        Policy::Name(name) if name == "free" => Ok(Editing::Free),
+        Policy::Name(name) => Err(de::Error::custom(format!(
+            "expected \"today\", \"free\" or a number of days, got `{}`",
+            name
+        ))),
+        Policy::Days(days) => Ok(Editing::Past(days)),
+    }
+}
+
+/// Theme - The colors of the UI
+/// Colors are named like "light_magenta", or given as "#rrggbb" or a 256 color index
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Titles and cells
+    #[serde(deserialize_with = "color")]
+    pub text: Color,
+    /// Habit names, headers and the stats panel
+    #[serde(deserialize_with = "color")]
+    pub accent: Color,
+    /// Text and background of the selected cell
+    #[serde(deserialize_with = "color")]
+    pub selected_fg: Color,
+    #[serde(deserialize_with = "color")]
+    pub selected_bg: Color,
+    /// Today, the command bar and days that don't count
+    #[serde(deserialize_with = "color")]
+    pub muted: Color,
+    /// Days that were done, partly done toward a goal, or missed
+    #[serde(deserialize_with = "color")]
+    pub done: Color,
+    #[serde(deserialize_with = "color")]
+    pub partial: Color,
+    #[serde(deserialize_with = "color")]
+    pub missed: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            text: Color::White,
+            accent: Color::LightMagenta,
+            selected_fg: Color::Magenta,
+            selected_bg: Color::White,
+            muted: Color::DarkGray,
+            done: Color::Green,
+            partial: Color::Yellow,
+            missed: Color::Red,
+        }
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_color(&name).ok_or_else(|| de::Error::custom(format!("unknown color `{}`", name)))
+}
+
+/// Parse a color name, a "#rrggbb" hex color or a 256 color index
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return match hex.len() {
+            6 => Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?)),
+            _ => None,
+        };
+    }
+    if let Ok(index) = name.parse::<u8>() {
+        return Some(Color::Indexed(index));
+    }
+    let name: String = name
+        .chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .collect::<String>()
+        .to_lowercase();
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// Glyphs - The characters of the cells, for every type of habit
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Glyphs {
+    pub bit: CellGlyphs,
+    pub count: CellGlyphs,
+    pub alpha: CellGlyphs,
+    pub number: CellGlyphs,
+}
+
+impl Glyphs {
+    pub fn of(&self, habit_type: &HabitType) -> &CellGlyphs {
+        match habit_type {
+            HabitType::BIT => &self.bit,
+            HabitType::COUNT => &self.count,
+            HabitType::ALPHA => &self.alpha,
+            HabitType::NUMBER => &self.number,
+        }
+    }
+}
+
+/// CellGlyphs - The characters shown for the days of one type of habit
+/// Counts, amounts and characters are shown as they are
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CellGlyphs {
+    /// A day marked done, for BIT habits
+    pub done: String,
+    /// A due day without an entry
+    pub missed: String,
+    /// A day the schedule doesn't ask for
+    pub not_due: String,
+    pub skipped: String,
+    /// A day with a note and no entry
+    pub note: String,
+}
+
+impl Default for CellGlyphs {
+    fn default() -> Self {
+        CellGlyphs {
+            done: String::from("•"),
+            missed: String::from("◦"),
+            not_due: String::from("·"),
+            skipped: String::from("-"),
+            note: String::from("✎"),
+        }
+    }
+}
+
+impl CellGlyphs {
+    /// The text of a cell, centered in 3 characters
+    /// A note only shows on a day without an entry
+    pub fn cell(&self, entry: Option<&Entry>, noted: bool, not_due: bool) -> String {
+        let glyph = match entry {
+            None if noted => self.note.to_owned(),
+            None if not_due => self.not_due.to_owned(),
+            None => self.missed.to_owned(),
+            Some(Entry::Done) => self.done.to_owned(),
+            Some(Entry::Skipped) => self.skipped.to_owned(),
+            Some(entry) => entry.symbol(),
+        };
+        format!("{:^3}", glyph)
+    }
+}
@@ -1,95 +1,525 @@
 use std::char;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::format;
-use std::fs::{self, create_dir_all, File};
-use std::io::prelude::*;
-use std::{ops::Add, vec};
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::{Add, Sub};
+use std::path::Path;
+use std::vec;
 
-use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use chrono_tz::Tz;
+use eyre::{bail, eyre, Result};
 use serde::{Deserialize, Serialize};
 
-// TODO: Change this?
-const FILE_NAME: &str = "habit.json";
-
-type D = DateTime<Utc>;
+use super::config::{self, Config, Grid};
+use super::migration::{self, FormatError, SCHEMA_VERSION};
+use super::schedule::{Schedule, Status};
+use super::storage::{self, LoadError};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum HabitType {
     BIT,
     COUNT,
     ALPHA,
+    NUMBER,
+}
+
+impl HabitType {
+    // Parse a habit type from its name, as used on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bit" => Some(HabitType::BIT),
+            "count" => Some(HabitType::COUNT),
+            "alpha" => Some(HabitType::ALPHA),
+            "number" => Some(HabitType::NUMBER),
+            _ => None,
+        }
+    }
+}
+
+/// Entry - The value a Habit is marked with on a day
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Entry {
+    /// A BIT habit was done
+    Done,
+    /// A COUNT habit was done this many times
+    Count(u32),
+    /// A NUMBER habit reached this amount
+    Amount(f64),
+    /// An ALPHA habit was marked with this character
+    Char(char),
+    /// The habit was deliberately skipped for the day
+    Skipped,
+}
+
+impl Entry {
+    // The text shown for the entry in a cell
+    pub fn symbol(&self) -> String {
+        match self {
+            Entry::Done => String::from("•"),
+            Entry::Count(n) => compact(*n as f64),
+            Entry::Amount(x) => compact(*x),
+            Entry::Char(c) => c.to_string(),
+            Entry::Skipped => String::from("-"),
+        }
+    }
+
+    // Whether the entry counts as the habit being done for the day
+    // Counts and amounts have to reach the goal if there is one
+    pub fn is_done(&self, goal: Option<f64>) -> bool {
+        match self {
+            Entry::Done | Entry::Char(_) => true,
+            Entry::Count(n) => goal.map_or(*n > 0, |goal| *n as f64 >= goal),
+            Entry::Amount(x) => goal.map_or(*x > 0.0, |goal| *x >= goal),
+            Entry::Skipped => false,
+        }
+    }
+
+    // The count or amount of the entry, if it has one
+    pub fn amount(&self) -> Option<f64> {
+        match self {
+            Entry::Count(n) => Some(*n as f64),
+            Entry::Amount(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+// Read a daily goal, 0 being no goal
+pub fn parse_goal(goal: &str) -> Result<f64, String> {
+    match goal.parse::<f64>() {
+        Ok(goal) if goal >= 0.0 && goal.is_finite() => Ok(goal),
+        _ => Err(format!(
+            "invalid goal `{}`, it must be a positive number",
+            goal
+        )),
+    }
+}
+
+// Format a number to fit in a cell of 3 characters
+// The number is rounded before the format is picked, so 9.96 is 10 rather than 10.0
+pub fn compact(x: f64) -> String {
+    let tenths = (x * 10.0).round() / 10.0;
+    if tenths < 10.0 && tenths.fract() != 0.0 {
+        format!("{:.1}", tenths)
+    } else if x.round() < 1000.0 {
+        format!("{:.0}", x.round())
+    } else {
+        format!("{:.0}k", (x / 1000.0).round().min(99.0))
+    }
 }
 
 /// Habit - Represents one Habit
+/// id is made from the first name of the habit, and stays the same when it is renamed or moved
 /// label is the name of the habit
-/// done_dates are the dates on which the Habit is marked as done
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+/// stats are the entries the Habit is marked with, by day
+/// notes are the notes left on the Habit, by day, whether the day has an entry or not
+/// schedule is the days the Habit is due on
+/// goal and unit describe the daily target of COUNT and NUMBER habits, like 8 glasses
+/// archived is the day the Habit was put away, it keeps its stats but leaves the grid
+/// group is the category the Habit is shown under, like "Morning"
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Habit {
+    pub id: String,
     pub label: String,
-    pub stats: HashMap<String, String>,
-    pub done_dates: Vec<String>, // For backwards compatibility
+    pub stats: BTreeMap<NaiveDate, Entry>,
+    pub notes: BTreeMap<NaiveDate, String>,
     pub habit_type: HabitType,
+    pub schedule: Schedule,
+    pub goal: Option<f64>,
+    pub unit: Option<String>,
+    pub archived: Option<NaiveDate>,
+    pub group: Option<String>,
 }
 
 impl Habit {
-    pub fn check_task(&mut self, date: String, val: Option<char>) {
+    // What the day means for the habit given its schedule, as of today,
+    // weekly quotas being counted in weeks starting on `week_start`
+    pub fn status(&self, day: NaiveDate, today: NaiveDate, week_start: Weekday) -> Status {
+        self.status_in_period(day, today, week_start, |start, end| {
+            self.done_between(start, end)
+        })
+    }
+
+    // What every day from `from` to `to`, both included, means for the habit, as of today
+    // The times a quota was met are counted once for each period rather than for each day
+    pub fn statuses(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        today: NaiveDate,
+        week_start: Weekday,
+    ) -> Vec<Status> {
+        let mut counted: Option<(NaiveDate, u32)> = None;
+        let mut statuses = vec![];
+        let mut day = from;
+        while day <= to {
+            let status =
+                self.status_in_period(day, today, week_start, |start, end| match counted {
+                    Some((counted_start, done)) if counted_start == start => done,
+                    _ => {
+                        let done = self.done_between(start, end);
+                        counted = Some((start, done));
+                        done
+                    }
+                });
+            statuses.push(status);
+            day += Duration::days(1);
+        }
+        statuses
+    }
+
+    // Number of days from `start` to `end`, both included, the habit was done on
+    fn done_between(&self, start: NaiveDate, end: NaiveDate) -> u32 {
+        self.stats
+            .range(start..=end)
+            .filter(|(_, entry)| entry.is_done(self.goal))
+            .count() as u32
+    }
+
+    // The status of the day, with `done_in` counting the days done in a quota period
+    fn status_in_period(
+        &self,
+        day: NaiveDate,
+        today: NaiveDate,
+        week_start: Weekday,
+        done_in: impl FnOnce(NaiveDate, NaiveDate) -> u32,
+    ) -> Status {
+        match self.stats.get(&day) {
+            Some(Entry::Skipped) => return Status::Skipped,
+            Some(entry) if entry.is_done(self.goal) => return Status::Done,
+            _ => {}
+        }
+        if !self.schedule.is_due(day) {
+            return Status::NotDue;
+        }
+
+        // Quotas are only missed once their period is over without being met
+        if let (Some(quota), Some((start, end))) =
+            (self.schedule.quota(), self.schedule.period(day, week_start))
+        {
+            return if done_in(start, end) >= quota {
+                Status::NotDue
+            } else if end < today {
+                Status::Missed
+            } else {
+                Status::Pending
+            };
+        }
+
+        if day < today {
+            Status::Missed
+        } else {
+            Status::Pending
+        }
+    }
+
+    pub fn check_task(&mut self, date: NaiveDate, val: Option<char>) {
         // This may or may not exist
         let existing_stat = self.stats.get(&date);
         match self.habit_type {
             HabitType::BIT => match existing_stat {
-                // Remove any existing stats for habit of type BIT
-                Some(_) => {
+                // Unmark a day that is already done
+                Some(Entry::Done) => {
                     self.stats.remove(&date);
                 }
-                // Add the stat if an existing stat doesn't exist
-                None => {
-                    self.stats.insert(date, String::from("true"));
+                // Otherwise mark it as done, replacing a skip
+                _ => {
+                    self.stats.insert(date, Entry::Done);
                 }
             },
             HabitType::COUNT => {
-                self.stats.insert(
-                    date.clone(),
-                    if self.stats.contains_key(&date) {
-                        (self.stats.get(&date).unwrap().parse::<i32>().unwrap() + 1).to_string()
-                    } else {
-                        String::from("0")
-                    },
-                );
+                let count = match existing_stat {
+                    Some(Entry::Count(n)) => n + 1,
+                    _ => 1,
+                };
+                self.stats.insert(date, Entry::Count(count));
+            }
+            HabitType::NUMBER => {
+                let amount = match existing_stat {
+                    Some(Entry::Amount(x)) => x + 1.0,
+                    _ => 1.0,
+                };
+                self.stats.insert(date, Entry::Amount(amount));
+            }
+            HabitType::ALPHA => {
+                if let Some(c) = val {
+                    self.stats.insert(date, Entry::Char(c));
+                }
+            }
+        }
+    }
+
+    // Take one off the count or amount for the date, removing the entry once it reaches 0
+    pub fn decrement(&mut self, date: NaiveDate) {
+        let entry = match self.stats.get(&date) {
+            Some(Entry::Count(n)) if *n > 1 => Entry::Count(n - 1),
+            Some(Entry::Amount(x)) if *x > 1.0 => Entry::Amount(x - 1.0),
+            Some(Entry::Count(_)) | Some(Entry::Amount(_)) => {
+                self.stats.remove(&date);
+                return;
             }
+            _ => return,
+        };
+        self.stats.insert(date, entry);
+    }
+
+    // Set the value for the date from text typed by the user
+    // Clearing a count or an amount to 0 removes the entry
+    pub fn set_value(&mut self, date: NaiveDate, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let entry = match self.habit_type {
+            HabitType::BIT => return Err(format!("`{}` takes no value", self.label)),
+            HabitType::COUNT => match value.parse::<u32>() {
+                Ok(0) => None,
+                Ok(n) => Some(Entry::Count(n)),
+                Err(_) => return Err(format!("`{}` is not a whole number", value)),
+            },
+            HabitType::NUMBER => match value.parse::<f64>() {
+                Ok(0.0) => None,
+                Ok(x) if x > 0.0 && x.is_finite() => Some(Entry::Amount(x)),
+                _ => return Err(format!("`{}` is not a positive number", value)),
+            },
             HabitType::ALPHA => {
-                self.stats.insert(date, val.unwrap().to_string());
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Entry::Char(c)),
+                    _ => return Err(format!("`{}` is not a single character", value)),
+                }
             }
+        };
+        match entry {
+            Some(entry) => self.stats.insert(date, entry),
+            None => self.stats.remove(&date),
+        };
+        Ok(())
+    }
+
+    // How far the entry for the date is toward the goal, between 0 and 1
+    pub fn progress(&self, date: NaiveDate) -> Option<f64> {
+        let goal = self.goal.filter(|goal| *goal > 0.0)?;
+        let amount = self.stats.get(&date)?.amount()?;
+        Some((amount / goal).min(1.0))
+    }
+
+    // Set the entry for the date, replacing any existing one
+    pub fn set_stat(&mut self, date: NaiveDate, entry: Entry) {
+        self.stats.insert(date, entry);
+    }
+
+    // Leave a note for the date, replacing the note left before but not the entry
+    pub fn set_note(&mut self, date: NaiveDate, note: String) {
+        self.notes.insert(date, note);
+    }
+
+    // Set the daily goal, 0 removing it, and the unit if one is given
+    pub fn set_goal(&mut self, goal: f64, unit: Option<String>) {
+        self.goal = Some(goal).filter(|goal| *goal > 0.0);
+        if unit.is_some() {
+            self.unit = unit;
+        }
+    }
+
+    // Put the habit in a group, an empty one taking it out of its group
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group.filter(|group| !group.is_empty());
+    }
+
+    // Skip the habit for the date, or undo the skip
+    pub fn toggle_skip(&mut self, date: NaiveDate) {
+        if self.stats.get(&date) == Some(&Entry::Skipped) {
+            self.stats.remove(&date);
+        } else {
+            self.stats.insert(date, Entry::Skipped);
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct HabitTracker {
-    pub start_date: D,
+    pub schema_version: u32,
+    // The first day in view, which isn't stored so that the grid always opens on today
+    // It is only set by `configure`, once the timezone that today is in is known
+    #[serde(skip, default = "unconfigured_start")]
+    pub start_date: NaiveDate,
     pub habits: Vec<Habit>,
+    // Timezone from the config that days are counted in, the system timezone if None
+    #[serde(skip)]
+    pub timezone: Option<Tz>,
+    // The days the grid shows, from the config
+    #[serde(skip)]
+    pub grid: Grid,
 }
 
 impl HabitTracker {
-    // Get the next week for the table
+    // Add a new habit to the tracker
+    pub fn add_habit(&mut self, label: String, habit_type: HabitType) {
+        let ids: Vec<&str> = self.habits.iter().map(|h| h.id.as_str()).collect();
+        self.habits.push(Habit {
+            id: new_id(&label, &ids),
+            stats: BTreeMap::new(),
+            notes: BTreeMap::new(),
+            habit_type,
+            label,
+            schedule: Schedule::Daily,
+            goal: None,
+            unit: None,
+            archived: None,
+            group: None,
+        });
+    }
+
+    // Add a habit with the settings it can be given when it is added
+    // The schedule is checked first, so that nothing is added if it is wrong
+    pub fn add_configured_habit(
+        &mut self,
+        label: String,
+        habit_type: HabitType,
+        schedule: Option<&str>,
+        goal: Option<f64>,
+        unit: Option<String>,
+        group: Option<String>,
+    ) -> Result<(), String> {
+        let schedule = match schedule {
+            Some(rule) => Schedule::parse(rule, self.today())?,
+            None => Schedule::Daily,
+        };
+        self.add_habit(label, habit_type);
+        let habit = self.habits.last_mut().unwrap();
+        habit.schedule = schedule;
+        habit.set_goal(goal.unwrap_or(0.0), unit);
+        habit.set_group(group);
+        Ok(())
+    }
+
+    // Change the days the habit is due on
+    pub fn set_schedule(&mut self, id: usize, rule: &str) -> Result<(), String> {
+        self.habits[id].schedule = Schedule::parse(rule, self.today())?;
+        Ok(())
+    }
+
+    // Archive the habit as of today, or bring it back to the grid
+    pub fn set_archived(&mut self, id: usize, archive: bool) -> Result<(), String> {
+        let today = self.today();
+        let habit = &mut self.habits[id];
+        match (archive, habit.archived) {
+            (true, Some(day)) => Err(format!("`{}` was already archived on {}", habit.label, day)),
+            (false, None) => Err(format!("`{}` isn't archived", habit.label)),
+            (true, None) => {
+                habit.archived = Some(today);
+                Ok(())
+            }
+            (false, Some(_)) => {
+                habit.archived = None;
+                Ok(())
+            }
+        }
+    }
+
+    // The indexes of the habits in the grid, leaving out the archived ones
+    pub fn active(&self) -> Vec<usize> {
+        (0..self.habits.len())
+            .filter(|&i| self.habits[i].archived.is_none())
+            .collect()
+    }
+
+    // The indexes of the archived habits
+    pub fn archived(&self) -> Vec<usize> {
+        (0..self.habits.len())
+            .filter(|&i| self.habits[i].archived.is_some())
+            .collect()
+    }
+
+    // Find a habit by its ID or its name (case insensitive),
+    // else by the start of its name or ID, else by the letters of its name in order
+    // Errors if nothing or more than one habit matches
+    pub fn find_habit(&self, query: &str) -> Result<usize, String> {
+        let query_lower = query.to_lowercase();
+        if query.is_empty() {
+            return Err(String::from("no habit given"));
+        }
+        if let Some(i) = self.habits.iter().position(|h| h.id == query) {
+            return Ok(i);
+        }
+        if let Some(i) = self
+            .habits
+            .iter()
+            .position(|h| h.label.to_lowercase() == query_lower)
+        {
+            return Ok(i);
+        }
+
+        let matching = |matches: &dyn Fn(&Habit) -> bool| -> Vec<usize> {
+            (0..self.habits.len())
+                .filter(|&i| matches(&self.habits[i]))
+                .collect()
+        };
+        let mut found = matching(&|h| {
+            h.label.to_lowercase().starts_with(&query_lower) || h.id.starts_with(&query_lower)
+        });
+        if found.is_empty() {
+            found = matching(&|h| is_subsequence(&query_lower, &h.label.to_lowercase()));
+        }
+        match found[..] {
+            [i] => Ok(i),
+            [] => Err(format!("no habit matches `{}`", query)),
+            _ => {
+                let names: Vec<&str> = found
+                    .iter()
+                    .map(|&i| self.habits[i].label.as_str())
+                    .collect();
+                Err(format!("`{}` matches {}", query, names.join(", ")))
+            }
+        }
+    }
+
+    // Count days in the timezone of the config, show its grid and open on today
+    pub fn configure(&mut self, config: &Config) {
+        self.timezone = config.timezone;
+        self.grid = config.grid;
+        self.this_week();
+    }
+
+    // The current calendar day in the configured timezone
+    pub fn today(&self) -> NaiveDate {
+        config::today(self.timezone)
+    }
+
+    // Move the view to the days containing today
+    pub fn this_week(&mut self) {
+        self.start_date = self.window_start(self.today());
+    }
+
+    // Show the days after the ones in view, a week unless the grid shows more or fewer
     pub fn next_week(&mut self) {
         let start_date = self.start_date;
-        self.start_date = start_date.add(Duration::days(7));
+        self.start_date = start_date.add(Duration::days(self.grid.days as i64));
     }
 
-    // Get the previous week on the table
+    // Show the days before the ones in view
     pub fn previous_week(&mut self) {
         let start_date = self.start_date;
-        self.start_date = start_date.checked_sub_signed(Duration::days(7)).unwrap();
+        self.start_date = start_date.sub(Duration::days(self.grid.days as i64));
+    }
+
+    // Move the view to the days containing the day, and return the column of the day
+    pub fn show_day(&mut self, day: NaiveDate) -> usize {
+        self.start_date = self.window_start(day);
+        (day - self.start_date).num_days() as usize
+    }
+
+    // Show the days of the grid from the config, around the first day in view
+    pub fn set_grid(&mut self, grid: Grid) {
+        self.grid = grid;
+        self.start_date = self.window_start(self.start_date);
     }
 
     // Get the date range
-    pub fn get_date_range(&self) -> Vec<D> {
-        let dates: &mut Vec<D> = &mut vec![];
-        dates.push(self.start_date);
-        for i in 1..7 {
-            dates.push(self.start_date.add(Duration::days(i)));
-        }
-        dates.to_owned()
+    pub fn get_date_range(&self) -> Vec<NaiveDate> {
+        (0..self.grid.days as i64)
+            .map(|i| self.start_date.add(Duration::days(i)))
+            .collect()
     }
 
     // Get the table header labels
@@ -101,37 +531,18 @@ impl HabitTracker {
             .collect()
     }
 
-    // Return the right values based on the stats map
-    pub fn values_v2(&self) -> Vec<Vec<Option<String>>> {
-        let date_range = self.get_date_range();
-        let mut values: Vec<Vec<Option<String>>> =
-            vec![vec![None; date_range.len()]; self.habits.len()];
-
-        for (i, habit) in self.habits.iter().enumerate() {
-            for (j, date) in date_range.iter().enumerate() {
-                if habit.stats.contains_key(&date.to_string()) {
-                    let stat = habit.stats.get(&date.to_string()).unwrap();
-                    // BUG: Fix this. Check for whitespaces and return None accordingly
-                    values[i][j] = if true { Some(stat.to_string()) } else { None }
-                }
-            }
-        }
-
-        values
-    }
-
-    // This should send a matrix of bools, which should contain
-    pub fn values(&self) -> Vec<Vec<bool>> {
+    // Return the entries of every habit for the dates in view
+    pub fn values_v2(&self) -> Vec<Vec<Option<Entry>>> {
         let date_range = self.get_date_range();
-        let mut values: Vec<Vec<bool>> = vec![vec![false; date_range.len()]; self.habits.len()];
-        for (i, habit) in self.habits.iter().enumerate() {
-            for (j, date) in date_range.iter().enumerate() {
-                if habit.done_dates.contains(&date.to_string()) {
-                    values[i][j] = true;
-                }
-            }
-        }
-        values
+        self.habits
+            .iter()
+            .map(|habit| {
+                date_range
+                    .iter()
+                    .map(|date| habit.stats.get(date).cloned())
+                    .collect()
+            })
+            .collect()
     }
 
     // Get all the labels from the disk
@@ -143,46 +554,174 @@ impl HabitTracker {
         labels
     }
 
-    // Get the week bound for the current view
-    fn week_bounds(week: u32) -> D {
-        let offset = chrono::offset::Local::now();
-        let current_year = offset.year();
-        let mon = NaiveDate::from_isoywd(current_year, week, Weekday::Mon);
-        DateTime::<Utc>::from_utc(mon.and_hms(0, 0, 0), Utc)
+    // The first of the days in view that contain the day
+    // Seven days are a week from the configured weekday, other numbers of days are laid
+    // out to end on today
+    fn window_start(&self, day: NaiveDate) -> NaiveDate {
+        let days = self.grid.days as i64;
+        if days == 7 {
+            return week_start(day, self.grid.week_start);
+        }
+        let today = self.today();
+        let windows_back = (today - day).num_days().div_euclid(days);
+        today - Duration::days(days - 1 + windows_back * days)
+    }
+
+    // Store the data on the disk
+    // The previous state is backed up first, and the new state is written atomically
+    // A state file that can't be parsed is never overwritten, it may still hold the user's data
+    pub fn store_state(&self, dir: &Path) -> Result<()> {
+        let path = storage::state_path(dir);
+        if let Err(e) = HabitTracker::read_state(&path) {
+            bail!("Refusing to overwrite the unreadable state: {}", e);
+        }
+        storage::backup_state(dir, false)?;
+        self.write_state(&path)
     }
 
-    // Convenience method to get the right config file
-    fn get_file_path() -> String {
-        let dir = dirs::config_dir().unwrap();
-        let rabit_dir = format!("{}/{}", dir.to_str().unwrap(), String::from("rabit"));
-        create_dir_all(&rabit_dir).unwrap();
-        String::from(format!("{}/{}", rabit_dir, FILE_NAME))
+    fn write_state(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string(self)?;
+        storage::write_atomic(path, data.as_bytes())
     }
 
-    // Store the data on the disk
-    pub fn store_state(&self) {
-        let file_path = HabitTracker::get_file_path();
-        println!("{:?}", file_path);
-        let mut file = File::create(file_path).unwrap();
-        file.write_all(serde_json::to_string(self).unwrap().as_bytes())
-            .unwrap();
+    // Parse a state written by any version of rabit, migrating it to the current schema
+    // Returns the tracker and the schema version it was written with
+    pub fn from_json(data: &str) -> Result<(Self, u32), FormatError> {
+        let mut value = serde_json::from_str(data).map_err(FormatError::Invalid)?;
+        let version = migration::migrate(&mut value)?;
+        let tracker = serde_json::from_value(value).map_err(FormatError::Invalid)?;
+        Ok((tracker, version))
+    }
+
+    // Read the state at the path, None if there is no state yet
+    fn read_state(path: &Path) -> Result<Option<(Self, u32)>, LoadError> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(LoadError::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        };
+        match HabitTracker::from_json(&data) {
+            Ok(state) => Ok(Some(state)),
+            Err(FormatError::Invalid(source)) => Err(LoadError::Corrupt {
+                path: path.to_owned(),
+                quarantine: None,
+                source,
+            }),
+            Err(FormatError::Unsupported(version)) => Err(LoadError::Unsupported {
+                path: path.to_owned(),
+                version,
+            }),
+        }
+    }
+
+    // Read the stored state again, without putting it aside if it is corrupt
+    // None if there is no state
+    pub fn reread_state(dir: &Path) -> Result<Option<Self>> {
+        let state = HabitTracker::read_state(&storage::state_path(dir))?;
+        Ok(state.map(|(tracker, _)| tracker))
     }
 
     // Fetch all the data from the disk
-    pub fn fetch_state() -> Self {
-        let file_path = HabitTracker::get_file_path();
-        let data = fs::read_to_string(file_path);
-        match data {
-            Ok(s) => serde_json::from_str(s.as_str()).unwrap(),
-            Err(_) => HabitTracker::default(),
+    // A corrupt state file is copied aside before the error is returned as a `LoadError`
+    pub fn fetch_state(dir: &Path) -> Result<Self> {
+        let path = storage::state_path(dir);
+        match HabitTracker::read_state(&path) {
+            Ok(None) => Ok(HabitTracker::default()),
+            Ok(Some((tracker, version))) => {
+                // Keep the file as it was before the migration, in case anything went wrong
+                if version < SCHEMA_VERSION {
+                    storage::backup_state(dir, true)?;
+                }
+                Ok(tracker)
+            }
+            Err(LoadError::Corrupt { path, source, .. }) => {
+                let quarantine = storage::quarantine(&path).ok();
+                Err(LoadError::Corrupt {
+                    path,
+                    quarantine,
+                    source,
+                }
+                .into())
+            }
+            Err(e) => Err(e.into()),
         }
     }
 
+    // Replace the stored state with one of the backups, 0 being the latest
+    // The current state is backed up first so that the restore itself can be undone
+    pub fn restore_backup(dir: &Path, index: usize) -> Result<Self> {
+        let backups = storage::list_backups(dir)?;
+        let path = backups
+            .get(index)
+            .ok_or_else(|| eyre!("No backup #{}, there are {} backups", index, backups.len()))?;
+        let (tracker, _) = HabitTracker::read_state(path)?
+            .ok_or_else(|| eyre!("Backup #{} no longer exists", index))?;
+        // A corrupt state was already put aside when it was loaded, only back up good ones
+        let state_path = storage::state_path(dir);
+        if HabitTracker::read_state(&state_path).is_ok() {
+            storage::backup_state(dir, true)?;
+        }
+        tracker.write_state(&state_path)?;
+        Ok(tracker)
+    }
+
     // Default impl
+    // Days are counted in the system timezone until the tracker is configured
     pub fn default() -> Self {
-        HabitTracker {
-            start_date: HabitTracker::week_bounds(Utc::now().iso_week().week()),
+        let mut tracker = HabitTracker {
+            schema_version: SCHEMA_VERSION,
+            start_date: unconfigured_start(),
             habits: vec![],
+            timezone: None,
+            grid: Grid::default(),
+        };
+        tracker.this_week();
+        tracker
+    }
+}
+
+// Where the view starts when the tracker is loaded, before it is configured
+fn unconfigured_start() -> NaiveDate {
+    NaiveDate::from_ymd(1970, 1, 1)
+}
+
+/// The first day of the week containing the day, weeks starting on `first`
+pub fn week_start(day: NaiveDate, first: Weekday) -> NaiveDate {
+    let since = (day.weekday().num_days_from_monday() + 7 - first.num_days_from_monday()) % 7;
+    day - Duration::days(since as i64)
+}
+
+/// A readable ID for a habit, made from its name and unique among the IDs taken,
+/// like `read-books` or `read-books-2`. IDs are never plain numbers, which stand for the list
+pub fn new_id(label: &str, taken: &[&str]) -> String {
+    let mut slug = String::new();
+    for c in label.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
         }
     }
+    let mut slug = slug.trim_end_matches('-').to_owned();
+    if slug.is_empty() || slug.chars().all(|c| c.is_ascii_digit()) {
+        slug = format!("habit-{}", slug).trim_end_matches('-').to_owned();
+    }
+    let mut id = slug.to_owned();
+    let mut n = 2;
+    while taken.contains(&id.as_str()) {
+        id = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    id
+}
+
+/// Whether the letters of `short` appear in `long` in the same order
+fn is_subsequence(short: &str, long: &str) -> bool {
+    let mut letters = long.chars();
+    short.chars().all(|c| letters.any(|l| l == c))
 }
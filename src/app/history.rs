@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::habit::{Entry, Habit, HabitType};
+use super::migration::SCHEMA_VERSION;
+use super::schedule::Schedule;
+use super::storage;
+
+const FILE_NAME: &str = "history.json";
+/// Number of changes that can be undone
+pub const MAX_CHANGES: usize = 100;
+
+/// Settings - What can be edited on a Habit besides its days
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Settings {
+    label: String,
+    habit_type: HabitType,
+    schedule: Schedule,
+    goal: Option<f64>,
+    unit: Option<String>,
+    group: Option<String>,
+}
+
+impl Settings {
+    fn of(habit: &Habit) -> Self {
+        Settings {
+            label: habit.label.clone(),
+            habit_type: habit.habit_type.clone(),
+            schedule: habit.schedule.clone(),
+            goal: habit.goal,
+            unit: habit.unit.clone(),
+            group: habit.group.clone(),
+        }
+    }
+
+    fn apply(&self, habit: &mut Habit) {
+        habit.label = self.label.clone();
+        habit.habit_type = self.habit_type.clone();
+        habit.schedule = self.schedule.clone();
+        habit.goal = self.goal;
+        habit.unit = self.unit.clone();
+        habit.group = self.group.clone();
+    }
+}
+
+/// Op - One step of a change, with what was there before so that it can be reversed
+/// Habits are found by their id, except when they are added or deleted at a position
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Op {
+    Add {
+        index: usize,
+        habit: Habit,
+    },
+    Delete {
+        index: usize,
+        habit: Habit,
+    },
+    Move {
+        id: String,
+        from: usize,
+        to: usize,
+    },
+    Edit {
+        id: String,
+        before: Settings,
+        after: Settings,
+    },
+    Archive {
+        id: String,
+        before: Option<NaiveDate>,
+        after: Option<NaiveDate>,
+    },
+    Mark {
+        id: String,
+        day: NaiveDate,
+        before: Option<Entry>,
+        after: Option<Entry>,
+    },
+    Note {
+        id: String,
+        day: NaiveDate,
+        before: Option<String>,
+        after: Option<String>,
+    },
+}
+
+impl Op {
+    /// The op that takes the habits back to where they were before this one
+    fn reverse(&self) -> Op {
+        match self.clone() {
+            Op::Add { index, habit } => Op::Delete { index, habit },
+            Op::Delete { index, habit } => Op::Add { index, habit },
+            Op::Move { id, from, to } => Op::Move {
+                id,
+                from: to,
+                to: from,
+            },
+            Op::Edit { id, before, after } => Op::Edit {
+                id,
+                before: after,
+                after: before,
+            },
+            Op::Archive { id, before, after } => Op::Archive {
+                id,
+                before: after,
+                after: before,
+            },
+            Op::Mark {
+                id,
+                day,
+                before,
+                after,
+            } => Op::Mark {
+                id,
+                day,
+                before: after,
+                after: before,
+            },
+            Op::Note {
+                id,
+                day,
+                before,
+                after,
+            } => Op::Note {
+                id,
+                day,
+                before: after,
+                after: before,
+            },
+        }
+    }
+
+    fn apply(&self, habits: &mut Vec<Habit>) {
+        let position = |habits: &[Habit], id: &str| habits.iter().position(|h| h.id == id);
+        match self {
+            Op::Add { index, habit } => habits.insert((*index).min(habits.len()), habit.clone()),
+            Op::Delete { habit, .. } => habits.retain(|h| h.id != habit.id),
+            Op::Move { id, to, .. } => {
+                if let Some(from) = position(habits, id) {
+                    let habit = habits.remove(from);
+                    habits.insert((*to).min(habits.len()), habit);
+                }
+            }
+            Op::Edit { id, after, .. } => {
+                if let Some(i) = position(habits, id) {
+                    after.apply(&mut habits[i]);
+                }
+            }
+            Op::Archive { id, after, .. } => {
+                if let Some(i) = position(habits, id) {
+                    habits[i].archived = *after;
+                }
+            }
+            Op::Mark { id, day, after, .. } => {
+                if let Some(i) = position(habits, id) {
+                    set(&mut habits[i].stats, *day, after.clone());
+                }
+            }
+            Op::Note { id, day, after, .. } => {
+                if let Some(i) = position(habits, id) {
+                    set(&mut habits[i].notes, *day, after.clone());
+                }
+            }
+        }
+    }
+}
+
+fn set<V>(map: &mut BTreeMap<NaiveDate, V>, day: NaiveDate, value: Option<V>) {
+    match value {
+        Some(value) => map.insert(day, value),
+        None => map.remove(&day),
+    };
+}
+
+/// Change - The ops that make up one change, in the order they were made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Change {
+    description: String,
+    ops: Vec<Op>,
+}
+
+/// History - The changes made to the habits, to undo and redo them
+/// It is kept next to the habits, and only used again if it leads to the habits on the disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    schema_version: u32,
+    // A hash of the habits the history leads to, to tell if they were changed elsewhere
+    fingerprint: u64,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    // The habits as they were last recorded, to find what changed
+    #[serde(skip)]
+    current: Vec<Habit>,
+}
+
+impl History {
+    /// A history that starts at the habits
+    pub fn new(habits: &[Habit]) -> Self {
+        History {
+            schema_version: SCHEMA_VERSION,
+            fingerprint: fingerprint(habits),
+            undo: vec![],
+            redo: vec![],
+            current: habits.to_vec(),
+        }
+    }
+
+    /// Load the history of the habits, a new one if there is none or it is of other habits,
+    /// which happens when they were changed elsewhere or migrated
+    pub fn load(dir: &Path, habits: &[Habit]) -> Self {
+        let history = fs::read_to_string(dir.join(FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str::<History>(&data).ok());
+        match history {
+            Some(history)
+                if history.schema_version == SCHEMA_VERSION
+                    && history.fingerprint == fingerprint(habits) =>
+            {
+                History {
+                    current: habits.to_vec(),
+                    ..history
+                }
+            }
+            _ => History::new(habits),
+        }
+    }
+
+    /// Store the history next to the habits
+    pub fn store(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(FILE_NAME);
+        storage::write_atomic(&path, serde_json::to_string(self)?.as_bytes())
+    }
+
+    /// Record the change from the habits last recorded to these, if they differ
+    /// Anything that was undone can't be redone anymore
+    /// Returns whether there was a change, and so the history has to be stored again
+    pub fn record(&mut self, habits: &[Habit]) -> bool {
+        if self.current == habits {
+            return false;
+        }
+        let ops = diff(&self.current, habits);
+        self.undo.push(Change {
+            description: describe(&ops, habits),
+            ops,
+        });
+        if self.undo.len() > MAX_CHANGES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.moved_to(habits);
+        true
+    }
+
+    /// Take the habits back to before the last change
+    /// Returns a description of the change, None if there is nothing to undo
+    pub fn undo(&mut self, habits: &mut Vec<Habit>) -> Option<String> {
+        let change = self.undo.pop()?;
+        change
+            .ops
+            .iter()
+            .rev()
+            .for_each(|op| op.reverse().apply(habits));
+        self.moved_to(habits);
+        let description = change.description.clone();
+        self.redo.push(change);
+        Some(description)
+    }
+
+    /// Make the last undone change again
+    /// Returns a description of the change, None if there is nothing to redo
+    pub fn redo(&mut self, habits: &mut Vec<Habit>) -> Option<String> {
+        let change = self.redo.pop()?;
+        change.ops.iter().for_each(|op| op.apply(habits));
+        self.moved_to(habits);
+        let description = change.description.clone();
+        self.undo.push(change);
+        Some(description)
+    }
+
+    /// Make the change from the habits last recorded to `ours` on top of `theirs`,
+    /// the habits as they were changed elsewhere in the meantime
+    /// The changes made elsewhere are taken as they are, they can't be undone here
+    pub fn rebase(&mut self, ours: &[Habit], theirs: &[Habit]) -> Vec<Habit> {
+        let mut habits = theirs.to_vec();
+        diff(&self.current, ours)
+            .iter()
+            .for_each(|op| op.apply(&mut habits));
+        self.moved_to(theirs);
+        habits
+    }
+
+    fn moved_to(&mut self, habits: &[Habit]) {
+        self.current = habits.to_vec();
+        self.fingerprint = fingerprint(habits);
+    }
+}
+
+/// A hash of the habits that stays the same between runs
+fn fingerprint(habits: &[Habit]) -> u64 {
+    storage::fingerprint(&serde_json::to_vec(habits).unwrap_or_default())
+}
+
+/// The ops that take the habits from before to after
+/// Deleted habits go first, added ones are put at the end and then moved in place with the rest
+fn diff(before: &[Habit], after: &[Habit]) -> Vec<Op> {
+    let mut ops = vec![];
+    let mut order: Vec<&str> = before.iter().map(|h| h.id.as_str()).collect();
+    for (index, habit) in before.iter().enumerate().rev() {
+        if !after.iter().any(|h| h.id == habit.id) {
+            ops.push(Op::Delete {
+                index,
+                habit: habit.clone(),
+            });
+            order.remove(index);
+        }
+    }
+    for habit in after {
+        if !before.iter().any(|h| h.id == habit.id) {
+            ops.push(Op::Add {
+                index: order.len(),
+                habit: habit.clone(),
+            });
+            order.push(&habit.id);
+        }
+    }
+    for (to, habit) in after.iter().enumerate() {
+        if order[to] != habit.id {
+            let from = order.iter().position(|id| *id == habit.id).unwrap_or(to);
+            ops.push(Op::Move {
+                id: habit.id.clone(),
+                from,
+                to,
+            });
+            let id = order.remove(from);
+            order.insert(to, id);
+        }
+    }
+
+    for a in after {
+        let b = match before.iter().find(|h| h.id == a.id) {
+            Some(b) => b,
+            None => continue,
+        };
+        let id = a.id.clone();
+        let (settings_b, settings_a) = (Settings::of(b), Settings::of(a));
+        if settings_b != settings_a {
+            ops.push(Op::Edit {
+                id: id.clone(),
+                before: settings_b,
+                after: settings_a,
+            });
+        }
+        if b.archived != a.archived {
+            ops.push(Op::Archive {
+                id: id.clone(),
+                before: b.archived,
+                after: a.archived,
+            });
+        }
+        for day in changed_days(&b.stats, &a.stats) {
+            ops.push(Op::Mark {
+                id: id.clone(),
+                day,
+                before: b.stats.get(&day).cloned(),
+                after: a.stats.get(&day).cloned(),
+            });
+        }
+        for day in changed_days(&b.notes, &a.notes) {
+            ops.push(Op::Note {
+                id: id.clone(),
+                day,
+                before: b.notes.get(&day).cloned(),
+                after: a.notes.get(&day).cloned(),
+            });
+        }
+    }
+    ops
+}
+
+fn changed_days<V: PartialEq>(
+    before: &BTreeMap<NaiveDate, V>,
+    after: &BTreeMap<NaiveDate, V>,
+) -> Vec<NaiveDate> {
+    let mut days: Vec<NaiveDate> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|day| before.get(day) != after.get(day))
+        .copied()
+        .collect();
+    days.sort();
+    days.dedup();
+    days
+}
+
+/// A short description of a change, from its first op
+fn describe(ops: &[Op], after: &[Habit]) -> String {
+    let label = |id: &str| {
+        after
+            .iter()
+            .find(|h| h.id == id)
+            .map(|h| h.label.to_owned())
+            .unwrap_or_default()
+    };
+    match ops.first() {
+        Some(Op::Add { habit, .. }) => format!("adding `{}`", habit.label),
+        Some(Op::Delete { habit, .. }) => format!("deleting `{}`", habit.label),
+        Some(Op::Move { .. }) => String::from("reordering the habits"),
+        Some(Op::Edit { before, after, .. }) if before.label != after.label => {
+            format!("renaming `{}` to `{}`", before.label, after.label)
+        }
+        Some(Op::Edit { after, .. }) => format!("editing `{}`", after.label),
+        Some(Op::Archive { id, after, .. }) => match after {
+            Some(_) => format!("archiving `{}`", label(id)),
+            None => format!("unarchiving `{}`", label(id)),
+        },
+        Some(Op::Mark { id, day, .. }) => format!("marking `{}` on {}", label(id), day),
+        Some(Op::Note { id, day, .. }) => format!("noting `{}` on {}", label(id), day),
+        None => String::from("a change"),
+    }
+}
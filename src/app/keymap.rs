@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+// Counts are capped so that `99999j` can't keep the cursor busy
+const MAX_COUNT: usize = 9999;
+/// How long an unfinished sequence waits for its next key, like the `timeoutlen` of vim
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Key - A key press, written like `g`, `G`, `<C-d>` or `<Space>` in key sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key {
+            code: event.code,
+            ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char('<') => String::from("lt"),
+            KeyCode::Char(c) if !self.ctrl => return write!(f, "{}", c),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => String::from("Enter"),
+            KeyCode::Tab => String::from("Tab"),
+            KeyCode::Backspace => String::from("BS"),
+            KeyCode::Esc => String::from("Esc"),
+            KeyCode::Up => String::from("Up"),
+            KeyCode::Down => String::from("Down"),
+            KeyCode::Left => String::from("Left"),
+            KeyCode::Right => String::from("Right"),
+            KeyCode::Home => String::from("Home"),
+            KeyCode::End => String::from("End"),
+            KeyCode::PageUp => String::from("PageUp"),
+            KeyCode::PageDown => String::from("PageDown"),
+            KeyCode::Delete => String::from("Del"),
+            code => format!("{:?}", code),
+        };
+        if self.ctrl {
+            write!(f, "<C-{}>", name)
+        } else {
+            write!(f, "<{}>", name)
+        }
+    }
+}
+
+/// Parse a key sequence like `gg`, `<C-d>` or `<Space>`, `<lt>` being `<`
+/// Keys are only named inside `<…>`, so `bs` is `b` then `s`
+pub fn parse_sequence(sequence: &str) -> Result<Vec<Key>, String> {
+    let mut keys = vec![];
+    let mut rest = sequence;
+    while let Some(c) = rest.chars().next() {
+        let name = match (c, rest.find('>')) {
+            ('<', Some(end)) if end > 1 => &rest[1..end],
+            _ => {
+                keys.push(Key {
+                    code: KeyCode::Char(c),
+                    ctrl: false,
+                });
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+        rest = &rest[name.len() + 2..];
+
+        let (ctrl, name) = match name.get(..2) {
+            Some("C-") | Some("c-") if name.len() > 2 => (true, &name[2..]),
+            _ => (false, name),
+        };
+        let code = match name.to_lowercase().as_str() {
+            "lt" => KeyCode::Char('<'),
+            _ => parse_key(name).ok_or_else(|| format!("unknown key `<{}>`", name))?,
+        };
+        keys.push(Key { code, ctrl });
+    }
+    if keys.is_empty() {
+        return Err(String::from("empty key sequence"));
+    }
+    Ok(keys)
+}
+
+/// Parse a single character or the name of a key
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let key = match name.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" | "cr" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" | "bs" => KeyCode::Backspace,
+        "esc" | "escape" => KeyCode::Esc,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" | "del" => KeyCode::Delete,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn sequence_to_string(keys: &[Key]) -> String {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+/// Action - What a key sequence does in NORMAL mode
+/// Most actions are repeated by a count typed before them, like `5j`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Go to the first habit, or to the row of the grid given as a count, from 1
+    FirstHabit,
+    /// Go to the last habit, or to the row of the grid given as a count, from 1
+    LastHabit,
+    FirstDay,
+    LastDay,
+    Today,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    /// Move the selected habit within its group
+    MoveUp,
+    MoveDown,
+    /// Fold the group of the selected habit, or unfold every group
+    Fold,
+    UnfoldAll,
+    Mark,
+    Decrement,
+    Value,
+    Command,
+    View,
+    AllHabits,
+    NextPage,
+    PreviousPage,
+    Undo,
+    Redo,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 29] = [
+        Action::Quit,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::FirstHabit,
+        Action::LastHabit,
+        Action::FirstDay,
+        Action::LastDay,
+        Action::Today,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::PageDown,
+        Action::PageUp,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Fold,
+        Action::UnfoldAll,
+        Action::Mark,
+        Action::Decrement,
+        Action::Value,
+        Action::Command,
+        Action::View,
+        Action::AllHabits,
+        Action::NextPage,
+        Action::PreviousPage,
+        Action::Undo,
+        Action::Redo,
+        Action::Help,
+    ];
+
+    /// The name of the action in the config and in `:map`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::FirstHabit => "first_habit",
+            Action::LastHabit => "last_habit",
+            Action::FirstDay => "first_day",
+            Action::LastDay => "last_day",
+            Action::Today => "today",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Fold => "fold",
+            Action::UnfoldAll => "unfold_all",
+            Action::Mark => "mark",
+            Action::Decrement => "decrement",
+            Action::Value => "value",
+            Action::Command => "command",
+            Action::View => "view",
+            Action::AllHabits => "all_habits",
+            Action::NextPage => "next_page",
+            Action::PreviousPage => "previous_page",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Help => "help",
+        }
+    }
+
+    /// What the action does, as shown in the help
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Up => "Previous habit",
+            Action::Down => "Next habit",
+            Action::Left => "Previous day",
+            Action::Right => "Next day",
+            Action::FirstHabit => "First habit, or row N",
+            Action::LastHabit => "Last habit, or row N",
+            Action::FirstDay => "First day in view",
+            Action::LastDay => "Last day in view",
+            Action::Today => "Go to today",
+            Action::HalfPageDown => "Half a screen of habits down",
+            Action::HalfPageUp => "Half a screen of habits up",
+            Action::PageDown => "A screen of habits down",
+            Action::PageUp => "A screen of habits up",
+            Action::MoveUp => "Move the habit up",
+            Action::MoveDown => "Move the habit down",
+            Action::Fold => "Fold the group of the habit",
+            Action::UnfoldAll => "Unfold all groups",
+            Action::Mark => "Mark the day",
+            Action::Decrement => "Count one less",
+            Action::Value => "Type a value",
+            Action::Command => "Command mode",
+            Action::View => "Week, month or year",
+            Action::AllHabits => "All habits in a month",
+            Action::NextPage => "Next week, month or year",
+            Action::PreviousPage => "Previous week, month or year",
+            Action::Undo => "Undo the last change",
+            Action::Redo => "Redo the last undone change",
+            Action::Help => "This help",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    // The sequences bound to the action when the config doesn't say otherwise
+    fn defaults(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Up => &["k", "<Up>"],
+            Action::Down => &["j", "<Down>"],
+            Action::Left => &["h", "<Left>"],
+            Action::Right => &["l", "<Right>"],
+            Action::FirstHabit => &["gg"],
+            Action::LastHabit => &["G"],
+            Action::FirstDay => &["0", "^"],
+            Action::LastDay => &["$"],
+            Action::Today => &["t"],
+            Action::HalfPageDown => &["<C-d>"],
+            Action::HalfPageUp => &["<C-u>"],
+            Action::PageDown => &["<C-f>", "<PageDown>"],
+            Action::PageUp => &["<C-b>", "<PageUp>"],
+            Action::MoveUp => &["K"],
+            Action::MoveDown => &["J"],
+            Action::Fold => &["za"],
+            Action::UnfoldAll => &["zR"],
+            Action::Mark => &["<Space>"],
+            Action::Decrement => &["-"],
+            Action::Value => &["<Enter>"],
+            Action::Command => &[":"],
+            Action::View => &["v"],
+            Action::AllHabits => &["a"],
+            Action::NextPage => &["]"],
+            Action::PreviousPage => &["["],
+            Action::Undo => &["u"],
+            Action::Redo => &["<C-r>"],
+            Action::Help => &["?"],
+        }
+    }
+}
+
+/// Sequences - One key sequence or a list of them, as written in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Sequences {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Sequences {
+    fn list(&self) -> Vec<&str> {
+        match self {
+            Sequences::One(sequence) => vec![sequence.as_str()],
+            Sequences::Many(sequences) => sequences.iter().map(|s| s.as_str()).collect(),
+        }
+    }
+}
+
+/// KeyMap - Resolves the keys typed in NORMAL mode to actions
+/// A count can be typed before a sequence, and a sequence is only run once it is complete
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    bindings: Vec<(Vec<Key>, Action)>,
+    pending: Vec<Key>,
+    count: Option<usize>,
+    // When the last key of the unfinished sequence or count was typed
+    last_key: Option<Instant>,
+}
+
+impl KeyMap {
+    /// The default bindings, with the actions in the config bound to their sequences instead
+    pub fn new(keys: &BTreeMap<String, Sequences>) -> Result<KeyMap, String> {
+        let mut keymap = KeyMap {
+            bindings: vec![],
+            pending: vec![],
+            count: None,
+            last_key: None,
+        };
+        let mut custom = vec![];
+        for (name, sequences) in keys {
+            let action =
+                Action::from_name(name).ok_or_else(|| format!("unknown action `{}`", name))?;
+            custom.push((action, sequences.list()));
+        }
+        for action in Action::ALL {
+            if !custom.iter().any(|(a, _)| *a == action) {
+                custom.push((action, action.defaults().to_vec()));
+            }
+        }
+        for (action, sequences) in custom {
+            for sequence in sequences {
+                keymap.insert(sequence, action, false)?;
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Bind a key sequence to an action, replacing what it was bound to
+    pub fn bind(&mut self, sequence: &str, action: Action) -> Result<(), String> {
+        self.insert(sequence, action, true)
+    }
+
+    // A sequence can't start another one, since it would run before the longer one is typed
+    fn insert(&mut self, sequence: &str, action: Action, replace: bool) -> Result<(), String> {
+        let keys = parse_sequence(sequence)?;
+        if let Some((other, other_action)) = self.bindings.iter().find(|(other, _)| {
+            other != &keys && (other.starts_with(&keys) || keys.starts_with(other))
+        }) {
+            return Err(format!(
+                "`{}` for {} clashes with `{}` for {}",
+                sequence_to_string(&keys),
+                action.name(),
+                sequence_to_string(other),
+                other_action.name()
+            ));
+        }
+        if let Some((_, existing)) = self.bindings.iter_mut().find(|(other, _)| *other == keys) {
+            if !replace && *existing != action {
+                return Err(format!(
+                    "`{}` is bound to both {} and {}",
+                    sequence_to_string(&keys),
+                    existing.name(),
+                    action.name()
+                ));
+            }
+            *existing = action;
+        } else {
+            self.bindings.push((keys, action));
+        }
+        Ok(())
+    }
+
+    /// Take a key typed in NORMAL mode
+    /// Returns the action once a sequence is complete, with the count typed before it if any
+    pub fn feed(&mut self, key: Key) -> Option<(Action, Option<usize>)> {
+        let now = Instant::now();
+        self.expire(now);
+        self.last_key = Some(now);
+        if key.code == KeyCode::Esc && !self.pending().is_empty() {
+            self.reset();
+            return None;
+        }
+
+        // A 0 that doesn't continue a count is a key of its own
+        if let KeyCode::Char(c @ '0'..='9') = key.code {
+            if self.pending.is_empty() && !key.ctrl && (c != '0' || self.count.is_some()) {
+                let digit = c.to_digit(10).unwrap() as usize;
+                self.count = Some((self.count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+                return None;
+            }
+        }
+
+        self.pending.push(key);
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| *keys == self.pending) {
+            let found = (*action, self.count);
+            self.reset();
+            return Some(found);
+        }
+        if !self
+            .bindings
+            .iter()
+            .any(|(keys, _)| keys.starts_with(&self.pending))
+        {
+            self.reset();
+        }
+        None
+    }
+
+    /// Forget the count and the keys of an unfinished sequence
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+        self.last_key = None;
+    }
+
+    /// Forget an unfinished sequence once no key was typed for `SEQUENCE_TIMEOUT`
+    /// Returns whether it was forgotten
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.last_key {
+            Some(last_key) if now.saturating_duration_since(last_key) >= SEQUENCE_TIMEOUT => {
+                self.reset();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The count and the keys typed so far, to show while a sequence is unfinished
+    pub fn pending(&self) -> String {
+        let count = self.count.map(|c| c.to_string()).unwrap_or_default();
+        count + &sequence_to_string(&self.pending)
+    }
+
+    /// Every action with the sequences bound to it, in the order of `Action::ALL`
+    pub fn bindings(&self) -> Vec<(Action, Vec<String>)> {
+        Action::ALL
+            .iter()
+            .map(|action| {
+                let sequences = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(keys, _)| sequence_to_string(keys))
+                    .collect();
+                (*action, sequences)
+            })
+            .collect()
+    }
+}
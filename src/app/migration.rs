@@ -0,0 +1,245 @@
+use chrono::NaiveDate;
+use serde_json::{json, Map, Value};
+
+use super::habit::new_id;
+
+/// Version of the state written by this build of rabit
+/// Bump it and add a migration to `MIGRATIONS` whenever the stored format changes
+pub const SCHEMA_VERSION: u32 = 10;
+
+/// A migration upgrades a state from the version at its index to the next version
+type Migration = fn(&mut Map<String, Value>);
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9,
+    v9_to_v10,
+];
+
+/// Errors for a state that can't be turned into the current schema
+#[derive(Debug)]
+pub enum FormatError {
+    /// The state isn't valid JSON, or doesn't match the schema after migrating
+    Invalid(serde_json::Error),
+    /// The state was written by a newer version of rabit
+    Unsupported(u64),
+}
+
+/// Upgrade a state written by any earlier version of rabit to the current schema
+/// Returns the version the state was written with
+pub fn migrate(state: &mut Value) -> Result<u32, FormatError> {
+    let state = match state.as_object_mut() {
+        Some(state) => state,
+        // Let the deserializer report what's wrong
+        None => return Ok(SCHEMA_VERSION),
+    };
+
+    // Files written before versioning have no schema_version
+    let version = state
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if version > SCHEMA_VERSION as u64 {
+        return Err(FormatError::Unsupported(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(state);
+    }
+    state.insert("schema_version".to_owned(), SCHEMA_VERSION.into());
+    Ok(version as u32)
+}
+
+/// v0 -> v1
+/// The oldest files only have `done_dates` on a habit, later ones added `stats` and
+/// `habit_type` but kept `done_dates` around. Fold the done dates into the stats
+fn v0_to_v1(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        let habit_type = habit
+            .entry("habit_type")
+            .or_insert_with(|| "BIT".into())
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let done_dates = habit.remove("done_dates");
+        let stats = habit
+            .entry("stats")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        // Done dates only ever meant "done", which has no equivalent for ALPHA habits
+        let done = match habit_type.as_str() {
+            "BIT" => "true",
+            "COUNT" => "1",
+            _ => continue,
+        };
+        if let (Some(stats), Some(Value::Array(dates))) = (stats.as_object_mut(), done_dates) {
+            for date in dates.iter().filter_map(Value::as_str) {
+                stats.entry(date.to_owned()).or_insert_with(|| done.into());
+            }
+        }
+    }
+}
+
+/// v1 -> v2
+/// Stats were keyed by `DateTime<Utc>::to_string()` with stringly typed values
+/// like "true", "3" or a single char. Key them by day with typed entries instead,
+/// dropping anything that can't be understood
+fn v1_to_v2(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        let habit_type = habit
+            .get("habit_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let stats = match habit.get_mut("stats").and_then(Value::as_object_mut) {
+            Some(stats) => stats,
+            None => continue,
+        };
+
+        let mut entries = Map::new();
+        for (key, value) in stats.iter() {
+            // "2022-07-11 00:00:00 UTC" -> "2022-07-11"
+            let day = match key.split_whitespace().next() {
+                Some(day) if NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok() => day,
+                _ => continue,
+            };
+            let value = value.as_str().unwrap_or_default();
+            let entry = match habit_type.as_str() {
+                "BIT" if value == "true" => json!("Done"),
+                "COUNT" => match value.parse::<u32>() {
+                    Ok(count) => json!({ "Count": count }),
+                    Err(_) => continue,
+                },
+                "ALPHA" if value.chars().count() == 1 => json!({ "Char": value }),
+                _ => continue,
+            };
+            entries.insert(day.to_owned(), entry);
+        }
+        *stats = entries;
+    }
+}
+
+/// v2 -> v3
+/// The week in view started at a `DateTime<Utc>` like "2022-07-11T00:00:00Z",
+/// keep the calendar day only
+fn v2_to_v3(state: &mut Map<String, Value>) {
+    let start_date = state
+        .get("start_date")
+        .and_then(Value::as_str)
+        .and_then(|s| s.get(..10))
+        .map(|s| s.to_owned());
+    if let Some(start_date) = start_date {
+        state.insert("start_date".to_owned(), start_date.into());
+    }
+}
+
+/// v3 -> v4
+/// Habits got a schedule, everything before was due daily
+fn v3_to_v4(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        habit.entry("schedule").or_insert_with(|| json!("Daily"));
+    }
+}
+
+/// v4 -> v5
+/// Habits got an optional daily goal and unit
+fn v4_to_v5(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        habit.entry("goal").or_insert(Value::Null);
+        habit.entry("unit").or_insert(Value::Null);
+    }
+}
+
+/// v5 -> v6
+/// Habits were only known by their place in the list, give them IDs made from their names
+fn v5_to_v6(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    let mut ids: Vec<String> = vec![];
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        let label = habit
+            .get("label")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let taken: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+        let id = new_id(label, &taken);
+        habit.insert("id".to_owned(), id.as_str().into());
+        ids.push(id);
+    }
+}
+
+/// v6 -> v7
+/// Habits can be archived, none were before
+fn v6_to_v7(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        habit.entry("archived").or_insert(Value::Null);
+    }
+}
+
+/// v7 -> v8
+/// Habits can be put in groups, none were before
+fn v7_to_v8(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        habit.entry("group").or_insert(Value::Null);
+    }
+}
+
+/// v8 -> v9
+/// The week in view was stored, which opened the grid on the week last looked at
+/// instead of today. It is only kept while rabit runs now
+fn v8_to_v9(state: &mut Map<String, Value>) {
+    state.remove("start_date");
+}
+
+/// v9 -> v10
+/// A note took the place of the entry of the day, so leaving one lost the entry and marking
+/// the day lost the note. Notes are kept next to the entries
+fn v9_to_v10(state: &mut Map<String, Value>) {
+    let habits = match state.get_mut("habits").and_then(Value::as_array_mut) {
+        Some(habits) => habits,
+        None => return,
+    };
+    for habit in habits.iter_mut().filter_map(Value::as_object_mut) {
+        let mut notes = Map::new();
+        if let Some(stats) = habit.get_mut("stats").and_then(Value::as_object_mut) {
+            let noted: Vec<String> = stats
+                .iter()
+                .filter(|(_, entry)| entry.get("Note").is_some())
+                .map(|(day, _)| day.to_owned())
+                .collect();
+            for day in noted {
+                if let Some(mut entry) = stats.remove(&day) {
+                    notes.insert(day, entry["Note"].take());
+                }
+            }
+        }
+        habit.insert("notes".to_owned(), notes.into());
+    }
+}
@@ -1,19 +1,39 @@
-use std::collections::HashMap;
-
-use commands::tokenizer::{tokenize, Token, TokenType};
 use eyre::Result;
 
-use self::habit::{Habit, HabitTracker};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use self::command::Command;
+use self::config::{Config, Editing};
+use self::habit::HabitTracker;
+use self::history::History;
+use self::keymap::{Action, Key, KeyMap};
+use self::prompt::Prompt;
+use self::stats::{completion_rate, current_streak, RATE_DAYS};
 
 pub mod ui;
 
 pub mod command;
+pub mod config;
 pub mod habit;
+pub mod history;
+pub mod keymap;
+pub mod migration;
+pub mod prompt;
+pub mod schedule;
+pub mod stats;
+pub mod storage;
 
 #[derive(Default, Debug, Clone)]
 pub struct AppState {
     selected_row: Option<usize>,
     selected_column: Option<usize>,
+    // The first line of the grid in view, and how many lines fit, as last drawn
+    offset: usize,
+    page: usize,
 }
 
 impl AppState {
@@ -28,6 +48,28 @@ impl AppState {
         self.selected_column = Some(col);
         self.selected_row = Some(row)
     }
+
+    /// Scroll as little as needed to show the line, `page` of the `lines` being in view
+    /// Returns the first line in view
+    pub fn scroll_to(&mut self, line: Option<usize>, page: usize, lines: usize) -> usize {
+        self.page = page;
+        match line {
+            Some(line) if line < self.offset => self.offset = line,
+            Some(line) if line >= self.offset + page => self.offset = line + 1 - page,
+            _ => {}
+        }
+        // Lines that were removed don't leave space at the bottom
+        self.offset = self.offset.min(lines.saturating_sub(page));
+        self.offset
+    }
+
+    /// How many lines were in view when the grid was last drawn, None before it was
+    pub fn page(&self) -> Option<usize> {
+        match self.page {
+            0 => None,
+            page => Some(page),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,43 +77,540 @@ pub enum AppMode {
     NORMAL,
     COMMAND,
     HABIT,
+    // Waiting for a yes or no before running a command
+    CONFIRM,
+}
+
+/// View - How the days of the habits are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// Every habit over one week
+    Week,
+    /// A calendar of the month, for the selected habit or for all habits together
+    Month { all: bool },
+    /// A heatmap of the year for the selected habit
+    Year,
+    /// The archived habits, with how they went
+    Archive,
+}
+
+/// Order - How the habits are sorted within their groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// The order they were added in or moved to
+    Manual,
+    Name,
+    /// Longest current streak first
+    Streak,
+    /// Highest completion rate over the last `RATE_DAYS` days first
+    Rate,
+}
+
+/// Line - A line of the grid, a group header or a habit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Group {
+        name: String,
+        folded: bool,
+        /// Number of habits in the group, shown when it is folded
+        count: usize,
+    },
+    Habit(usize),
 }
 
 #[derive(Debug, Clone)]
 pub struct App {
     pub mode: AppMode,
+    pub view: View,
+    pub order: Order,
+    // The groups whose habits are hidden
+    pub folded: BTreeSet<String>,
     pub state: AppState,
     pub tracker: HabitTracker,
     pub input: String,
+    // The directory the habits, their history and the config are kept in
+    pub dir: PathBuf,
+    pub config: Config,
+    // When the config file was last changed, to reload it
+    config_modified: Option<SystemTime>,
+    // A hash of the habits file as it was last read or written here, to take in the
+    // changes made to it elsewhere, like by `rabit mark` from cron, instead of overwriting them
+    stored: Option<u64>,
+    pub keymap: KeyMap,
+    // Bindings made with `:map`, kept when the config is reloaded
+    maps: Vec<(String, Action)>,
+    // Whether the key bindings are shown over the grid
+    pub show_help: bool,
+    pub history: History,
+    // The cursor and history of the command bar
+    pub prompt: Prompt,
+    // The command waiting to be confirmed
+    confirming: Option<Command>,
+    // Whether past days can be changed whatever the editing policy, after `:backfill`
+    pub backfill: bool,
 }
 
 impl App {
-    /// Create a new app
-    pub fn new() -> Result<App> {
+    /// Create a new app around a tracker loaded from the directory
+    pub fn new(dir: PathBuf, tracker: HabitTracker, config: Config) -> Result<App> {
+        let history = History::load(&dir, &tracker.habits);
         let app = App {
             state: AppState::default(),
-            tracker: HabitTracker::fetch_state(),
+            tracker,
             mode: AppMode::NORMAL,
+            view: View::Week,
+            order: Order::Manual,
+            folded: BTreeSet::new(),
             input: String::new(),
+            keymap: config.keymap()?,
+            config,
+            config_modified: config::modified(&dir),
+            stored: storage::state_fingerprint(&dir),
+            maps: vec![],
+            show_help: false,
+            history,
+            prompt: Prompt::load(&dir),
+            dir,
+            confirming: None,
+            backfill: false,
         };
         Ok(app)
     }
 
+    /// Load the config again if the file changed since it was last read
+    /// An invalid config is reported in the command bar, and the previous one is kept
+    pub fn reload_config(&mut self) {
+        let modified = config::modified(&self.dir);
+        if modified == self.config_modified {
+            return;
+        }
+        self.config_modified = modified;
+        match Config::load(&self.dir).and_then(|config| Ok((config.keymap()?, config))) {
+            Ok((mut keymap, config)) => {
+                for (sequence, action) in &self.maps {
+                    // These were valid on top of the previous keys, but may clash with the new ones
+                    let _ = keymap.bind(sequence, *action);
+                }
+                self.tracker.timezone = config.timezone;
+                if config.grid != self.config.grid {
+                    self.set_grid(config.grid);
+                }
+                self.keymap = keymap;
+                self.config = config;
+            }
+            Err(e) => self.input = format!("Error! {}", e),
+        }
+    }
+
+    /// Show other days in the grid, keeping the selected day in view
+    fn set_grid(&mut self, grid: config::Grid) {
+        let selected = self.state.selected();
+        let day = selected.map(|(_, col)| self.tracker.get_date_range()[col]);
+        self.tracker.set_grid(grid);
+        if let (Some((row, _)), Some(day)) = (selected, day) {
+            let col = self.tracker.show_day(day);
+            self.state.select((row, col));
+        }
+    }
+
+    /// Run an action of NORMAL mode, with the count typed before its keys
+    /// Quitting is left to the caller
+    pub fn run_action(&mut self, action: Action, count: Option<usize>) {
+        let times = count.unwrap_or(1);
+        match action {
+            Action::Quit => {}
+            Action::Up => (0..times).for_each(|_| self.move_cursor_up()),
+            Action::Down => (0..times).for_each(|_| self.move_cursor_down()),
+            Action::Left => (0..times).for_each(|_| self.move_cursor_left()),
+            Action::Right => (0..times).for_each(|_| self.move_cursor_right()),
+            // Counts number the rows from 1, like lines in vim
+            Action::FirstHabit => self.select_habit(count.map_or(0, |n| n.saturating_sub(1))),
+            Action::LastHabit => {
+                let last = self.rows().len().saturating_sub(1);
+                self.select_habit(count.map_or(last, |n| n.saturating_sub(1)))
+            }
+            Action::FirstDay => self.select_day(0),
+            Action::LastDay => self.select_day(self.tracker.get_date_range().len() - 1),
+            Action::Today => self.go_to(self.tracker.today()),
+            Action::HalfPageDown | Action::HalfPageUp | Action::PageDown | Action::PageUp => {
+                let page = self.state.page().unwrap_or(self.rows().len());
+                let step = match action {
+                    Action::HalfPageDown | Action::HalfPageUp => (page / 2).max(1),
+                    _ => page.max(1),
+                };
+                let row = self.state.selected().map_or(0, |(row, _)| row);
+                self.select_habit(match action {
+                    Action::HalfPageDown | Action::PageDown => row + step * times,
+                    _ => row.saturating_sub(step * times),
+                });
+            }
+            Action::Mark => self.mark_habit(),
+            Action::Decrement => (0..times).for_each(|_| self.decrement_habit()),
+            Action::Value => self.enter_value_mode(),
+            Action::Command => self.enter_command_mode(),
+            Action::View => self.toggle_view(),
+            Action::AllHabits => self.toggle_all_habits(),
+            Action::NextPage => self.turn_page(times as i32),
+            Action::PreviousPage => self.turn_page(-(times as i32)),
+            Action::MoveUp => self.move_habit(-(times as i32)),
+            Action::MoveDown => self.move_habit(times as i32),
+            Action::Fold => {
+                if let Err(e) = self.fold(None) {
+                    self.input = format!("Error! {}", e);
+                }
+            }
+            Action::UnfoldAll => self.keep_selection(|app| app.folded.clear()),
+            Action::Undo => (0..times).for_each(|_| self.undo()),
+            Action::Redo => (0..times).for_each(|_| self.redo()),
+            Action::Help => self.show_help = !self.show_help,
+        }
+    }
+
+    /// The lines of the grid: the habits without a group, then every group with its habits
+    /// Groups come in the order of their first habit, archived habits only show in the archive
+    pub fn lines(&self) -> Vec<Line> {
+        let habits = &self.tracker.habits;
+        let mut sorted = match self.view {
+            View::Archive => self.tracker.archived(),
+            _ => self.tracker.active(),
+        };
+        let (today, week_start) = (self.tracker.today(), self.tracker.grid.week_start);
+        match self.order {
+            Order::Manual => {}
+            Order::Name => sorted.sort_by_key(|&i| habits[i].label.to_lowercase()),
+            Order::Streak => {
+                sorted.sort_by_key(|&i| Reverse(current_streak(&habits[i], today, week_start)))
+            }
+            Order::Rate => {
+                let from = today - Duration::days(RATE_DAYS - 1);
+                let rate = |i: usize| completion_rate(&habits[i], from, today, today, week_start);
+                sorted.sort_by(|&a, &b| rate(b).total_cmp(&rate(a)));
+            }
+        }
+        if self.view == View::Archive {
+            return sorted.into_iter().map(Line::Habit).collect();
+        }
+
+        let mut groups: Vec<&str> = vec![];
+        for i in self.tracker.active() {
+            match &habits[i].group {
+                Some(group) if !groups.contains(&group.as_str()) => groups.push(group),
+                _ => {}
+            }
+        }
+        let members = |group: Option<&str>| -> Vec<usize> {
+            sorted
+                .iter()
+                .copied()
+                .filter(|&i| habits[i].group.as_deref() == group)
+                .collect()
+        };
+        let mut lines: Vec<Line> = members(None).into_iter().map(Line::Habit).collect();
+        for group in groups {
+            let members = members(Some(group));
+            let folded = self.folded.contains(group);
+            lines.push(Line::Group {
+                name: group.to_owned(),
+                folded,
+                count: members.len(),
+            });
+            if !folded {
+                lines.extend(members.into_iter().map(Line::Habit));
+            }
+        }
+        lines
+    }
+
+    /// The indexes of the habits in the rows of the view, the lines that can be selected
+    pub fn rows(&self) -> Vec<usize> {
+        self.lines()
+            .into_iter()
+            .filter_map(|line| match line {
+                Line::Habit(i) => Some(i),
+                Line::Group { .. } => None,
+            })
+            .collect()
+    }
+
+    /// The index of the habit in the selected row
+    pub fn selected_habit(&self) -> Option<usize> {
+        let (row, _) = self.state.selected()?;
+        self.rows().get(row).copied()
+    }
+
+    /// Make a change to the rows, keeping the same habit selected if it still shows
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let selected = self.selected_habit();
+        change(self);
+        let row = selected.and_then(|habit| self.rows().iter().position(|&i| i == habit));
+        match (row, self.state.selected()) {
+            (Some(row), Some((_, col))) => self.state.select((row, col)),
+            _ => self.clamp_selection(),
+        }
+    }
+
+    /// Move the selected habit some rows down, or up if negative, within its group
+    /// Only the manual order can be changed, the others follow the habits
+    fn move_habit(&mut self, rows: i32) {
+        let selected = match self.selected_habit() {
+            Some(selected) if self.view != View::Archive => selected,
+            _ => return,
+        };
+        if self.order != Order::Manual {
+            self.input = "Error! habits are only moved in the manual order, see `sort`".to_owned();
+            return;
+        }
+        let group = self.tracker.habits[selected].group.to_owned();
+        // The habits it can trade places with, in their order
+        let mut neighbours: Vec<usize> = self
+            .rows()
+            .into_iter()
+            .filter(|&i| self.tracker.habits[i].group == group)
+            .collect();
+        let from = neighbours.iter().position(|&i| i == selected).unwrap();
+        let to = (from as i32 + rows).clamp(0, neighbours.len() as i32 - 1) as usize;
+        if from == to {
+            return;
+        }
+        // Only these habits are put in a new order, on the places they already took
+        let places = neighbours.to_owned();
+        let habit = neighbours.remove(from);
+        neighbours.insert(to, habit);
+        let before = self.tracker.habits.clone();
+        for (&place, i) in places.iter().zip(neighbours) {
+            self.tracker.habits[place] = before[i].clone();
+        }
+        // The selection follows the habit to its new place
+        if let Some(((_, col), row)) = self
+            .state
+            .selected()
+            .zip(self.rows().iter().position(|&i| i == places[to]))
+        {
+            self.state.select((row, col));
+        }
+        self.save();
+    }
+
+    /// Fold a group or unfold it, the group of the selected habit if none is given
+    fn fold(&mut self, group: Option<String>) -> Result<(), String> {
+        let group = match group {
+            Some(name) => self
+                .tracker
+                .habits
+                .iter()
+                .filter_map(|habit| habit.group.to_owned())
+                .find(|group| group.eq_ignore_ascii_case(&name))
+                .ok_or(format!("no group `{}`", name))?,
+            None => {
+                let habit =
+                    &self.tracker.habits[self.selected_habit().ok_or("no habit selected")?];
+                habit
+                    .group
+                    .to_owned()
+                    .ok_or(format!("`{}` is in no group", habit.label))?
+            }
+        };
+        self.keep_selection(|app| {
+            if !app.folded.remove(&group) {
+                app.folded.insert(group);
+            }
+        });
+        Ok(())
+    }
+
+    /// Select the habit in the row, or the last one if there are fewer, keeping the day
+    fn select_habit(&mut self, row: usize) {
+        let count = self.rows().len();
+        if count == 0 {
+            return;
+        }
+        let col = self.state.selected().map_or(0, |(_, col)| col);
+        self.state.select((row.min(count - 1), col));
+    }
+
+    /// Select the day in the column of the days in view, keeping the habit
+    fn select_day(&mut self, col: usize) {
+        if self.rows().is_empty() {
+            return;
+        }
+        let row = self.state.selected().map_or(0, |(row, _)| row);
+        self.state.select((row, col));
+    }
+
     /// Enter the command mode, to execute the following commands
     /// This will enable the user command bar input and take commands for execution
     /// Sets the App.mode to COMMAND
     pub fn enter_command_mode(&mut self) {
         self.input = String::new();
+        self.prompt.reset();
         self.mode = AppMode::COMMAND;
     }
 
+    /// Edit the command being typed, completing habit IDs and names with Tab
+    pub fn edit_input(&mut self, key: Key) {
+        let mut habits: Vec<String> = vec![];
+        for word in self
+            .tracker
+            .habits
+            .iter()
+            .map(|h| &h.id)
+            .chain(self.tracker.habits.iter().map(|h| &h.label))
+        {
+            if !habits.contains(word) {
+                habits.push(word.to_owned());
+            }
+        }
+        self.prompt.edit(&mut self.input, key, &habits);
+    }
+
     /// Execute a command fed into the command buffer
     /// If the command is wrong display the help text
     pub fn execute_input(&mut self) {
-        if let Ok(tokens) = tokenize(&self.input.clone()) {
-            self.handle_commands(tokens);
-        } else {
-            self.input = "[2] Error! please use format `add 'habit name'`".to_owned();
+        // Mistyped commands are kept too, to be fixed
+        if let Err(e) = self.prompt.push(&self.dir, &self.input) {
+            self.input = format!("Error! could not save the command history: {}", e);
+            return;
+        }
+        match command::parse(&self.input) {
+            Ok(command) => {
+                self.input = String::new();
+                if let Err(e) = self.run_command(command, false) {
+                    self.input = format!("Error! {}", e);
+                }
+            }
+            Err(e) => self.input = format!("Error! {}", e),
+        }
+    }
+
+    /// Run a parsed command, the error is shown in the command bar
+    /// Commands that throw away habits are only run once they are confirmed
+    fn run_command(&mut self, command: Command, confirmed: bool) -> Result<(), String> {
+        let question = match &command {
+            Command::Delete { habit } => {
+                let habit = &self.tracker.habits[self.tracker.find_habit(habit)?];
+                Some((
+                    format!("Delete `{}`?", habit.label),
+                    // Delete the habit that was asked about, whatever was typed
+                    Command::Delete {
+                        habit: habit.id.to_owned(),
+                    },
+                ))
+            }
+            Command::Restore { index } => {
+                Some((format!("Restore backup #{}?", index), command.clone()))
+            }
+            _ => None,
+        };
+        if let (Some((question, command)), false) = (question, confirmed) {
+            self.input = format!("{} (y/n)", question);
+            self.confirming = Some(command);
+            self.mode = AppMode::CONFIRM;
+            return Ok(());
+        }
+
+        match command {
+            Command::Add {
+                label,
+                habit_type,
+                schedule,
+                goal,
+                unit,
+                group,
+            } => {
+                self.tracker.add_configured_habit(
+                    label,
+                    habit_type,
+                    schedule.as_deref(),
+                    goal,
+                    unit,
+                    group,
+                )?;
+                self.save();
+            }
+            Command::Edit { habit, label } => {
+                let id = self.tracker.find_habit(&habit)?;
+                self.tracker.habits[id].label = label;
+                self.save();
+            }
+            Command::Goal { habit, goal, unit } => {
+                let id = self.tracker.find_habit(&habit)?;
+                self.tracker.habits[id].set_goal(goal, unit);
+                self.save();
+            }
+            Command::Schedule { habit, rule } => {
+                let id = self.tracker.find_habit(&habit)?;
+                self.tracker.set_schedule(id, &rule)?;
+                self.save();
+            }
+            Command::Delete { habit } => {
+                let id = self.tracker.find_habit(&habit)?;
+                self.tracker.habits.remove(id);
+                self.clamp_selection();
+                self.save();
+            }
+            Command::Archive { habit } => self.archive(habit, true)?,
+            Command::Unarchive { habit } => self.archive(habit, false)?,
+            Command::Group { habit, group } => {
+                let id = self.tracker.find_habit(&habit)?;
+                self.keep_selection(|app| app.tracker.habits[id].set_group(group));
+                self.save();
+            }
+            Command::Fold { group } => self.fold(group)?,
+            Command::Sort(order) => self.keep_selection(|app| app.order = order),
+            Command::Skip => self.skip_habit(),
+            Command::Note { text } => self.note_habit(text),
+            Command::Restore { index } => self.restore_backup(index),
+            Command::View(view) => self.show_view(view),
+            Command::Goto(date) => self.go_to(date),
+            Command::Backfill => {
+                self.backfill = !self.backfill;
+                self.input = String::from(if self.backfill {
+                    "Backfilling, past days can be changed until `:backfill` again"
+                } else {
+                    "Stopped backfilling"
+                });
+            }
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::Map { binding: None } => self.show_help = true,
+            Command::Map {
+                binding: Some((sequence, action)),
+            } => {
+                self.keymap.bind(&sequence, action)?;
+                self.maps.push((sequence, action));
+            }
+        }
+        Ok(())
+    }
+
+    /// Archive a habit or bring it back to the grid, the selected one if none is given
+    fn archive(&mut self, habit: Option<String>, archive: bool) -> Result<(), String> {
+        let id = match habit {
+            Some(habit) => self.tracker.find_habit(&habit)?,
+            None => self.selected_habit().ok_or("no habit selected")?,
+        };
+        self.tracker.set_archived(id, archive)?;
+        let label = &self.tracker.habits[id].label;
+        self.input = match archive {
+            true => format!("Archived `{}`, see `view archive`", label),
+            false => format!("Unarchived `{}`", label),
+        };
+        self.clamp_selection();
+        self.save();
+        Ok(())
+    }
+
+    /// Answer the question asked before running a command
+    pub fn confirm(&mut self, yes: bool) {
+        self.mode = AppMode::NORMAL;
+        self.input = String::new();
+        match self.confirming.take() {
+            Some(command) if yes => {
+                if let Err(e) = self.run_command(command, true) {
+                    self.input = format!("Error! {}", e);
+                }
+            }
+            _ => self.input = "Cancelled".to_owned(),
         }
     }
 
@@ -79,55 +618,307 @@ impl App {
     /// Pushed a new habit with the name to the tracker
     /// TODO: Take in more complex habits
     pub fn add_habit(&mut self, habit: String, habit_type: habit::HabitType) {
-        self.tracker.habits.push(Habit {
-            stats: HashMap::new(),
-            habit_type,
-            label: habit,
-            done_dates: vec![],
-        });
+        self.tracker.add_habit(habit, habit_type);
+        self.save();
     }
 
-    /// Mark Habit as done or undone based on the given state
-    pub fn mark_habit(&mut self) {
-        if self.state.selected().is_none() {
+    /// Persist the tracker after every change, so nothing is lost if rabit is killed
+    /// The change is recorded in the history to be undone. Errors are shown in the command bar
+    pub fn save(&mut self) {
+        self.reload_state();
+        if let Err(e) = self.tracker.store_state(&self.dir) {
+            self.input = format!("Error! could not save: {}", e);
+            return;
+        }
+        self.stored = storage::state_fingerprint(&self.dir);
+        if self.history.record(&self.tracker.habits) {
+            self.store_history();
+        }
+    }
+
+    fn store_history(&mut self) {
+        if let Err(e) = self.history.store(&self.dir) {
+            self.input = format!("Error! could not save the history: {}", e);
+        }
+    }
+
+    /// Take in the changes made to the stored habits elsewhere since they were last read
+    /// or written here. A change made here and not saved yet is made again on top of them
+    pub fn reload_state(&mut self) {
+        let stored = storage::state_fingerprint(&self.dir);
+        if stored == self.stored {
             return;
         }
-        let (row, col) = self.state.selected().unwrap();
+        // An unreadable file is reported once, and left alone when saving
+        self.stored = stored;
+        match HabitTracker::reread_state(&self.dir) {
+            Ok(Some(tracker)) => {
+                self.tracker.habits = self.history.rebase(&self.tracker.habits, &tracker.habits);
+                self.clamp_selection();
+            }
+            Ok(None) => {}
+            Err(e) => self.input = format!("Error! {}", e),
+        }
+    }
+
+    /// Undo the last change to the habits
+    pub fn undo(&mut self) {
+        // What was changed elsewhere stays, only the change made here is undone
+        self.reload_state();
+        match self.history.undo(&mut self.tracker.habits) {
+            Some(description) => self.restore_habits("Undid", description),
+            None => self.input = "Nothing to undo".to_owned(),
+        }
+    }
+
+    /// Make the last undone change again
+    pub fn redo(&mut self) {
+        self.reload_state();
+        match self.history.redo(&mut self.tracker.habits) {
+            Some(description) => self.restore_habits("Redid", description),
+            None => self.input = "Nothing to redo".to_owned(),
+        }
+    }
+
+    fn restore_habits(&mut self, verb: &str, description: String) {
+        self.clamp_selection();
+        self.input = format!("{} {}", verb, description);
+        match self.tracker.store_state(&self.dir) {
+            Ok(()) => {
+                self.stored = storage::state_fingerprint(&self.dir);
+                self.store_history();
+            }
+            Err(e) => self.input = format!("Error! could not save: {}", e),
+        }
+    }
+
+    /// Keep the selection on a habit after some were removed or hidden
+    fn clamp_selection(&mut self) {
+        if let Some((row, col)) = self.state.selected() {
+            match self.rows().len() {
+                0 => self.state = AppState::default(),
+                len if row >= len => self.state.select((len - 1, col)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Replace the tracker with one of the backups, 0 being the latest
+    pub fn restore_backup(&mut self, index: usize) {
+        match HabitTracker::restore_backup(&self.dir, index) {
+            Ok(tracker) => {
+                self.tracker = tracker;
+                self.tracker.configure(&self.config);
+                self.stored = storage::state_fingerprint(&self.dir);
+                self.state = AppState::default();
+                self.input = format!("Restored backup #{}", index);
+                // Restoring can be undone like any other change
+                if self.history.record(&self.tracker.habits) {
+                    self.store_history();
+                }
+            }
+            Err(e) => self.input = format!("Error! {}", e),
+        }
+    }
+
+    /// The index of the selected habit and the day of the selected column
+    /// Archived habits are only looked at, so there is none in the archive
+    fn selected_day(&self) -> Option<(usize, NaiveDate)> {
+        if self.view == View::Archive {
+            return None;
+        }
+        let (_, col) = self.state.selected()?;
         let date = self.tracker.get_date_range()[col];
+        Some((self.selected_habit()?, date))
+    }
+
+    /// Whether the day can be changed, by the editing policy of the config unless backfilling
+    pub fn can_edit(&self, day: NaiveDate) -> Result<(), String> {
+        let editing = if self.backfill {
+            Editing::Free
+        } else {
+            self.config.editing
+        };
+        editing.check(day, self.tracker.today())
+    }
+
+    /// The selected habit and day if the day can be changed, the reason why not is shown if not
+    fn editable_day(&mut self) -> Option<(usize, NaiveDate)> {
+        let (row, date) = self.selected_day()?;
+        match self.can_edit(date) {
+            Ok(()) => Some((row, date)),
+            Err(e) => {
+                self.input = format!("Error! {}", e);
+                None
+            }
+        }
+    }
+
+    /// Mark Habit as done or undone based on the given state
+    pub fn mark_habit(&mut self) {
+        let (row, date) = match self.editable_day() {
+            Some(selected) => selected,
+            None => return,
+        };
         let habit = &mut self.tracker.habits[row];
         match habit.habit_type {
             habit::HabitType::BIT => {
-                habit.check_task(date.to_string(), None);
+                habit.check_task(date, None);
+                self.save();
             }
             habit::HabitType::COUNT => {
-                habit.check_task(date.to_string(), Some('1'));
+                habit.check_task(date, None);
+                self.save();
             }
             habit::HabitType::ALPHA => {
                 // Enter command mode to take in the char input
                 self.mode = AppMode::HABIT;
             }
+            habit::HabitType::NUMBER => self.enter_value_mode(),
         }
     }
 
-    /// Mark the completion of habit entry
-    pub fn complete_mark_habit(&mut self, c: char) {
-        if self.state.selected().is_none() {
+    /// Enter HABIT mode to type the value of a COUNT or NUMBER habit for the selected day
+    /// The input starts with the current value so that it can be corrected
+    pub fn enter_value_mode(&mut self) {
+        let (row, date) = match self.editable_day() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let habit = &self.tracker.habits[row];
+        if !matches!(
+            habit.habit_type,
+            habit::HabitType::COUNT | habit::HabitType::NUMBER
+        ) {
             return;
         }
-        let (row, col) = self.state.selected().unwrap();
-        let date = self.tracker.get_date_range()[col];
+        self.input = match habit.stats.get(&date).and_then(|e| e.amount()) {
+            Some(amount) => amount.to_string(),
+            None => String::new(),
+        };
+        self.mode = AppMode::HABIT;
+    }
+
+    /// Whether HABIT mode takes a typed value rather than a single char
+    pub fn entering_value(&self) -> bool {
+        match self.selected_habit() {
+            Some(i) => self.tracker.habits[i].habit_type != habit::HabitType::ALPHA,
+            None => false,
+        }
+    }
+
+    /// Set the value typed in HABIT mode for the selected day
+    pub fn complete_value_entry(&mut self) {
+        let (row, date) = match self.editable_day() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let value = std::mem::take(&mut self.input);
+        match self.tracker.habits[row].set_value(date, &value) {
+            Ok(()) => self.save(),
+            Err(e) => self.input = format!("Error! {}", e),
+        }
+    }
+
+    /// Take one off the count or amount of the selected habit for the selected day
+    pub fn decrement_habit(&mut self) {
+        if let Some((row, date)) = self.editable_day() {
+            self.tracker.habits[row].decrement(date);
+            self.save();
+        }
+    }
+
+    /// Mark the completion of habit entry
+    pub fn complete_mark_habit(&mut self, c: char) {
+        let (row, date) = match self.editable_day() {
+            Some(selected) => selected,
+            None => return,
+        };
         let habit = &mut self.tracker.habits[row];
-        habit.check_task(date.to_string(), Some(c));
+        habit.check_task(date, Some(c));
+        self.save();
+    }
+
+    /// Skip the selected habit for the selected day, or undo the skip
+    pub fn skip_habit(&mut self) {
+        if let Some((row, date)) = self.editable_day() {
+            self.tracker.habits[row].toggle_skip(date);
+            self.save();
+        }
+    }
+
+    /// Leave a note on the selected habit for the selected day
+    pub fn note_habit(&mut self, note: String) {
+        if let Some((row, date)) = self.editable_day() {
+            self.tracker.habits[row].set_note(date, note);
+            self.save();
+        }
+    }
+
+    /// Switch from the week to the month view, to the year view and back
+    pub fn toggle_view(&mut self) {
+        self.show_view(match self.view {
+            View::Week => View::Month { all: false },
+            View::Month { .. } => View::Year,
+            View::Year | View::Archive => View::Week,
+        });
+    }
+
+    /// Switch to a view, which shows other habits in the case of the archive
+    /// Most views need a habit, the first one is selected on today if there is none
+    fn show_view(&mut self, view: View) {
+        self.view = view;
+        self.clamp_selection();
+        if self.state.selected().is_none() && !self.rows().is_empty() {
+            let col = self.tracker.show_day(self.tracker.today());
+            self.state.select((0, col));
+        }
+    }
+
+    /// Switch the month view between the selected habit and all habits
+    pub fn toggle_all_habits(&mut self) {
+        if let View::Month { all } = self.view {
+            self.view = View::Month { all: !all };
+        }
+    }
+
+    /// Move the selection some weeks, months or years ahead, or back if negative,
+    /// depending on the view
+    fn turn_page(&mut self, pages: i32) {
+        let date = match self.selected_day() {
+            Some((_, date)) => date,
+            None => return,
+        };
+        self.go_to(match self.view {
+            View::Week => date + Duration::days((self.tracker.grid.days as i64) * pages as i64),
+            View::Month { .. } => add_months(date, pages),
+            View::Year => add_months(date, 12 * pages),
+            View::Archive => return,
+        });
+    }
+
+    /// Show the days around a date and select it on the selected habit, the first if none is
+    /// The archive has no days, it goes back to the week
+    pub fn go_to(&mut self, date: NaiveDate) {
+        if self.view == View::Archive {
+            self.show_view(View::Week);
+        }
+        let col = self.tracker.show_day(date);
+        if !self.rows().is_empty() {
+            let row = self.state.selected().map_or(0, |(row, _)| row);
+            self.state.select((row, col));
+        }
     }
 
     /// Move the cursor down
     pub fn move_cursor_down(&mut self) {
-        if !(self.tracker.habits.len() > 1) {
+        let count = self.rows().len();
+        if count < 2 {
             return;
         }
         let i = match self.state.selected() {
             Some((row, col)) => {
-                if row == self.tracker.values().len() - 1 {
+                if row == count - 1 {
                     (0, col)
                 } else {
                     (row + 1, col)
@@ -140,13 +931,14 @@ impl App {
 
     /// Move cursor up
     pub fn move_cursor_up(&mut self) {
-        if !(self.tracker.habits.len() > 1) {
+        let count = self.rows().len();
+        if count < 2 {
             return;
         }
         let i = match self.state.selected() {
             Some((row, col)) => {
                 if row == 0 {
-                    (self.tracker.values().len() - 1, col)
+                    (count - 1, col)
                 } else {
                     (row - 1, col)
                 }
@@ -158,7 +950,7 @@ impl App {
 
     /// Move cursor left
     pub fn move_cursor_left(&mut self) {
-        if !(self.tracker.habits.len() > 0) {
+        if self.rows().is_empty() {
             return;
         }
 
@@ -166,7 +958,7 @@ impl App {
             Some((row, col)) => {
                 if col == 0 {
                     self.tracker.previous_week();
-                    (row, self.tracker.values()[0].len() - 1)
+                    (row, self.tracker.get_date_range().len() - 1)
                 } else {
                     (row, col - 1)
                 }
@@ -178,12 +970,12 @@ impl App {
 
     /// Move cursor right
     pub fn move_cursor_right(&mut self) {
-        if !(self.tracker.habits.len() > 0) {
+        if self.rows().is_empty() {
             return;
         }
         let i = match self.state.selected() {
             Some((row, col)) => {
-                let length = self.tracker.values()[0].len();
+                let length = self.tracker.get_date_range().len();
                 if col == length - 1 {
                     self.tracker.next_week();
                     (row, 0)
@@ -195,91 +987,14 @@ impl App {
         };
         self.state.select(i)
     }
+}
 
-    /// Helper method to handle command inputs
-    /// Private method for being called from {execute_input}
-    fn handle_commands(&mut self, tokens: Vec<Token>) {
-        match tokens[0].text {
-            // Add new habit
-            // example: `add {HABIT_NAME}`
-            // TODO: Add better command erroring
-            "add" => {
-                let err_str = "[1] Error! please use format `add 'habit name'`".to_owned();
-                let err_str_3 = "[3] Error! please use format `add 'habit name'`".to_owned();
-                let length = tokens.len();
-                if length == 3 && tokens[1].token_type != TokenType::Whitespace {
-                    self.input = err_str;
-                    return;
-                } else {
-                    if length != 3 && length != 5 {
-                        self.input = format!("Len - {:?}", length);
-                        println!("Invalid length {:?}", length);
-                        return;
-                    }
-                    // Handle commands with types
-                    if length > 3 && tokens[3].token_type != TokenType::Whitespace {
-                        self.input = err_str_3;
-                        return;
-                    }
-                    // Handle habit types of APHA and COUNT
-                    let mut habit_type = habit::HabitType::BIT;
-                    if length == 5 {
-                        let type_token = tokens[4].text;
-                        match type_token.parse::<i32>() {
-                            Ok(i) => {
-                                // The type token is an integer, and hence the habit type can be count
-                                habit_type = habit::HabitType::COUNT;
-                            }
-                            Err(_) => {
-                                habit_type = habit::HabitType::ALPHA;
-                            }
-                        }
-                    }
-                    let new_habit = tokens[2].text.into();
-                    self.add_habit(new_habit, habit_type);
-                    return;
-                }
-            }
-            // Edit an existing habit
-            // example: `edit {HABIT_ID} {NEW_HABIT_NAME}`
-            "edit" => {
-                if tokens.len() != 5
-                    && tokens[2].token_type != TokenType::Whitespace
-                    && tokens[4].token_type != TokenType::Whitespace
-                {
-                    self.input = "[1] Error! please use format `edit 1 'habit name'`".to_owned();
-                    return;
-                } else {
-                    if let Ok(id) = tokens[2].text.parse::<usize>() {
-                        if id > self.tracker.habits.len() {
-                            return;
-                        }
-                        self.tracker.habits[id].label = tokens[4].text.into();
-                    }
-                    return;
-                }
-            }
-            // Delete an existing habit
-            // example: `delete {HABIT_ID}`
-            "delete" => {
-                if tokens.len() != 3 && tokens[2].token_type != TokenType::Whitespace {
-                    self.input = "[1] Error! please use format `add 'habit name'`".to_owned();
-                    return;
-                } else {
-                    if let Ok(id) = tokens[2].text.parse::<usize>() {
-                        if id > self.tracker.habits.len() {
-                            return;
-                        }
-                        self.tracker.habits.remove(id);
-                    }
-                }
-            }
-
-            _ => {
-                self.input = "[3] only add, edit & delete supported'`".to_owned();
-                return;
-            }
-        }
-        return;
-    }
+/// The same day some months later, or the last day of that month if it is shorter
+fn add_months(day: NaiveDate, months: i32) -> NaiveDate {
+    let index = day.year() * 12 + day.month0() as i32 + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    (1..=day.day())
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .unwrap()
 }
@@ -0,0 +1,244 @@
+use std::fs;
+use std::path::Path;
+
+use crossterm::event::KeyCode;
+use eyre::Result;
+
+use super::command::{completions, tokenize};
+use super::keymap::Key;
+use super::storage;
+
+const FILE_NAME: &str = "command_history.json";
+/// Number of commands kept in the history
+const MAX_COMMANDS: usize = 100;
+
+/// Prompt - The editing state of the command bar, and the commands entered before
+/// The text itself is `App.input`, the cursor is a byte offset into it
+#[derive(Debug, Clone, Default)]
+pub struct Prompt {
+    cursor: usize,
+    history: Vec<String>,
+    // The entry of the history shown, and the text that was typed before going through it
+    browsing: Option<usize>,
+    draft: String,
+    completion: Option<Completion>,
+}
+
+/// Completion - The words that can replace the one being typed, cycled through with Tab
+#[derive(Debug, Clone)]
+struct Completion {
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Prompt {
+    /// A prompt with the commands entered before, an empty history if they can't be read
+    pub fn load(dir: &Path) -> Self {
+        let history = fs::read_to_string(dir.join(FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str::<Vec<String>>(&data).ok())
+            .unwrap_or_default();
+        Prompt {
+            history,
+            ..Prompt::default()
+        }
+    }
+
+    /// Add an entered command to the history and store it in the directory
+    pub fn push(&mut self, dir: &Path, command: &str) -> Result<()> {
+        let command = command.trim();
+        if command.is_empty() || self.history.last().map(|c| c.as_str()) == Some(command) {
+            return Ok(());
+        }
+        self.history.push(command.to_owned());
+        if self.history.len() > MAX_COMMANDS {
+            self.history.remove(0);
+        }
+        let path = dir.join(FILE_NAME);
+        storage::write_atomic(&path, serde_json::to_string(&self.history)?.as_bytes())
+    }
+
+    /// Start editing an empty command
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+        self.browsing = None;
+        self.draft = String::new();
+        self.completion = None;
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Edit the command with a key, `habits` being the IDs and names to complete
+    pub fn edit(&mut self, input: &mut String, key: Key, habits: &[String]) {
+        self.cursor = self.cursor.min(input.len());
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.completion = None;
+        }
+        // Editing a command from the history makes it the one to look for
+        let browsing = matches!(
+            (key.code, key.ctrl),
+            (KeyCode::Up | KeyCode::Down, _) | (KeyCode::Char('p' | 'n'), true)
+        );
+        if !browsing {
+            self.browsing = None;
+        }
+        match (key.code, key.ctrl) {
+            (KeyCode::Char('w'), true) => {
+                let end = self.cursor;
+                let trimmed = input[..end].trim_end();
+                // Whitespace can be wider than a byte, like a non-breaking space
+                let start = trimmed
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| c.is_whitespace())
+                    .map(|(i, c)| i + c.len_utf8())
+                    .unwrap_or(0);
+                input.replace_range(start..end, "");
+                self.cursor = start;
+            }
+            (KeyCode::Char('u'), true) => {
+                input.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            (KeyCode::Char('a'), true) | (KeyCode::Home, _) => self.cursor = 0,
+            (KeyCode::Char('e'), true) | (KeyCode::End, _) => self.cursor = input.len(),
+            (KeyCode::Char('p'), true) | (KeyCode::Up, _) => self.previous(input),
+            (KeyCode::Char('n'), true) | (KeyCode::Down, _) => self.next(input),
+            (KeyCode::Char(_), true) => {}
+            (KeyCode::Char(c), false) => {
+                input.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            (KeyCode::Backspace, _) => {
+                if let Some(c) = input[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    input.remove(self.cursor);
+                }
+            }
+            (KeyCode::Delete, _) if self.cursor < input.len() => {
+                input.remove(self.cursor);
+            }
+            (KeyCode::Left, _) => {
+                if let Some(c) = input[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                }
+            }
+            (KeyCode::Right, _) => {
+                if let Some(c) = input[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            (KeyCode::Tab, _) => self.complete(input, habits, true),
+            (KeyCode::BackTab, _) => self.complete(input, habits, false),
+            _ => {}
+        }
+    }
+
+    /// Show the previous command in the history that starts like the typed one
+    fn previous(&mut self, input: &mut String) {
+        if self.browsing.is_none() {
+            self.draft = input.to_owned();
+        }
+        let end = self.browsing.unwrap_or(self.history.len());
+        let found = self.history[..end]
+            .iter()
+            .rposition(|c| c.starts_with(&self.draft));
+        if let Some(i) = found {
+            self.show(input, Some(i));
+        }
+    }
+
+    /// Show the next command in the history that starts like the typed one, or the typed one
+    fn next(&mut self, input: &mut String) {
+        let start = match self.browsing {
+            Some(i) => i + 1,
+            None => return,
+        };
+        let found = self.history[start..]
+            .iter()
+            .position(|c| c.starts_with(&self.draft))
+            .map(|i| start + i);
+        self.show(input, found);
+    }
+
+    fn show(&mut self, input: &mut String, entry: Option<usize>) {
+        self.browsing = entry;
+        *input = match entry {
+            Some(i) => self.history[i].to_owned(),
+            None => self.draft.to_owned(),
+        };
+        self.cursor = input.len();
+    }
+
+    /// Complete the word before the cursor, or go to the next or previous completion
+    fn complete(&mut self, input: &mut String, habits: &[String], forward: bool) {
+        if let Some(completion) = &mut self.completion {
+            let count = completion.candidates.len();
+            completion.index = match forward {
+                true => (completion.index + 1) % count,
+                false => (completion.index + count - 1) % count,
+            };
+            let (start, word) = (
+                completion.start,
+                quote(&completion.candidates[completion.index]),
+            );
+            input.replace_range(start..self.cursor, &word);
+            self.cursor = start + word.len();
+            return;
+        }
+
+        let before = &input[..self.cursor];
+        // A word that is still in quotes is completed as if they were closed
+        let (tokens, open) = match tokenize(before) {
+            Ok(tokens) => (tokens, false),
+            Err(_) => match tokenize(&format!("{}'", before))
+                .or_else(|_| tokenize(&format!("{}\"", before)))
+            {
+                Ok(tokens) => (tokens, true),
+                Err(_) => return,
+            },
+        };
+        let typing = open || !before.is_empty() && !before.ends_with(char::is_whitespace);
+        let (previous, start, word) = match tokens.split_last() {
+            Some((last, previous)) if typing => (previous, last.start, last.text.to_owned()),
+            _ => (&tokens[..], self.cursor, String::new()),
+        };
+
+        let prefix = word.to_lowercase();
+        let mut candidates: Vec<String> = vec![];
+        for candidate in completions(previous, &word, habits) {
+            if candidate.to_lowercase().starts_with(&prefix) && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+        if candidates.is_empty() {
+            return;
+        }
+        let index = if forward { 0 } else { candidates.len() - 1 };
+        let replacement = quote(&candidates[index]);
+        input.replace_range(start..self.cursor, &replacement);
+        self.cursor = start + replacement.len();
+        // A single candidate is done with, more of them can be cycled through
+        if candidates.len() > 1 {
+            self.completion = Some(Completion {
+                start,
+                candidates,
+                index,
+            });
+        }
+    }
+}
+
+/// Quote a word that has spaces or quotes in it
+fn quote(word: &str) -> String {
+    if word.is_empty()
+        || word.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'))
+    {
+        format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        word.to_owned()
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use super::habit;
+
+/// Schedule - The days a Habit is due on
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub enum Schedule {
+    /// Due every day
+    #[default]
+    Daily,
+    /// Due on these days of the week
+    Weekdays(Vec<Weekday>),
+    /// Due every so many days, counting from a day
+    EveryNDays { every: u32, from: NaiveDate },
+    /// Due until it was done so many times in the week, starting on the configured weekday
+    PerWeek(u32),
+    /// Due until it was done so many times in the month
+    PerMonth(u32),
+}
+
+/// Status - What a day means for a Habit, given its schedule
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    /// The habit was done
+    Done,
+    /// The habit was deliberately skipped
+    Skipped,
+    /// The schedule didn't ask for the habit on that day
+    NotDue,
+    /// The habit is due, but there is still time to do it
+    Pending,
+    /// The habit was due and wasn't done
+    Missed,
+}
+
+impl Schedule {
+    /// Parse a schedule as written in commands
+    /// `daily`, `mon,wed,fri`, `every:3` (from today), `3/week` or `10/month`
+    pub fn parse(rule: &str, today: NaiveDate) -> Result<Self, String> {
+        let rule = rule.trim().to_lowercase();
+        if rule == "daily" {
+            return Ok(Schedule::Daily);
+        }
+        if let Some(every) = rule.strip_prefix("every:") {
+            return match every.parse::<u32>() {
+                Ok(every) if every > 0 => Ok(Schedule::EveryNDays { every, from: today }),
+                _ => Err(format!("`{}` is not a number of days", every)),
+            };
+        }
+        if let Some((times, period)) = rule.split_once('/') {
+            let times = match times.parse::<u32>() {
+                Ok(times) if times > 0 => times,
+                _ => return Err(format!("`{}` is not a number of times", times)),
+            };
+            return match period {
+                "week" => Ok(Schedule::PerWeek(times)),
+                "month" => Ok(Schedule::PerMonth(times)),
+                _ => Err(format!("`{}` should be `week` or `month`", period)),
+            };
+        }
+
+        let mut days = vec![];
+        for day in rule.split(',') {
+            match day.trim().parse::<Weekday>() {
+                Ok(day) if !days.contains(&day) => days.push(day),
+                Ok(_) => {}
+                Err(_) => return Err(format!("`{}` is not a schedule", rule)),
+            }
+        }
+        days.sort_by_key(|d| d.num_days_from_monday());
+        Ok(Schedule::Weekdays(days))
+    }
+
+    /// The days of the quota period containing the day, for PerWeek and PerMonth,
+    /// weeks starting on `week_start`
+    pub fn period(&self, day: NaiveDate, week_start: Weekday) -> Option<(NaiveDate, NaiveDate)> {
+        match self {
+            Schedule::PerWeek(_) => {
+                let start = habit::week_start(day, week_start);
+                Some((start, start + Duration::days(6)))
+            }
+            Schedule::PerMonth(_) => {
+                let start = day.with_day(1).unwrap();
+                let next = match start.month() {
+                    12 => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+                    m => NaiveDate::from_ymd(start.year(), m + 1, 1),
+                };
+                Some((start, next - Duration::days(1)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the habit is due on the day, regardless of how often it was done
+    /// Quota schedules are due on every day until the quota is met
+    pub fn is_due(&self, day: NaiveDate) -> bool {
+        match self {
+            Schedule::Daily | Schedule::PerWeek(_) | Schedule::PerMonth(_) => true,
+            Schedule::Weekdays(days) => days.contains(&day.weekday()),
+            Schedule::EveryNDays { every, from } => {
+                day >= *from && (day - *from).num_days() % *every as i64 == 0
+            }
+        }
+    }
+
+    /// Number of times the habit has to be done in a quota period
+    pub fn quota(&self) -> Option<u32> {
+        match self {
+            Schedule::PerWeek(n) | Schedule::PerMonth(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Daily => write!(f, "daily"),
+            Schedule::Weekdays(days) => {
+                let days: Vec<String> = days.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "{}", days.join(","))
+            }
+            Schedule::EveryNDays { every, .. } => write!(f, "every:{}", every),
+            Schedule::PerWeek(n) => write!(f, "{}/week", n),
+            Schedule::PerMonth(n) => write!(f, "{}/month", n),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use super::habit::{self, Entry, Habit};
+use super::schedule::Status;
+
+/// Number of days the completion rate in the summary is computed over
+pub const RATE_DAYS: i64 = 30;
+
+/// Stats - A summary of one habit up to a day, as shown in the side panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Completion rate over the last `RATE_DAYS` days, between 0 and 1
+    pub completion_rate: f64,
+    /// Sum of the counts or amounts in the week and the month of the day,
+    /// for COUNT and NUMBER habits
+    pub week_total: f64,
+    pub month_total: f64,
+    /// How often every character was entered, most frequent first, for ALPHA habits
+    pub histogram: Vec<(char, u32)>,
+}
+
+impl Stats {
+    /// The summary as of today, the week total being of the week starting on `week_start`
+    pub fn new(habit: &Habit, today: NaiveDate, week_start: Weekday) -> Self {
+        let week = habit::week_start(today, week_start);
+        let month_start = today.with_day(1).unwrap();
+        let mut histogram: Vec<(char, u32)> = histogram(habit, None, today).into_iter().collect();
+        histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        Stats {
+            current_streak: current_streak(habit, today, week_start),
+            longest_streak: longest_streak(habit, today, week_start),
+            completion_rate: completion_rate(
+                habit,
+                today - Duration::days(RATE_DAYS - 1),
+                today,
+                today,
+                week_start,
+            ),
+            week_total: total(habit, week, today),
+            month_total: total(habit, month_start, today),
+            histogram,
+        }
+    }
+}
+
+/// Number of due days in a row the habit was done, up to today
+/// Today not being done yet doesn't break the streak, and skipped days or days
+/// the schedule doesn't ask for are passed over
+pub fn current_streak(habit: &Habit, today: NaiveDate, week_start: Weekday) -> u32 {
+    let first = match habit.stats.keys().next() {
+        Some(first) => *first,
+        None => return 0,
+    };
+
+    let mut streak = 0;
+    for status in habit
+        .statuses(first, today, today, week_start)
+        .into_iter()
+        .rev()
+    {
+        match status {
+            Status::Done => streak += 1,
+            Status::Missed => break,
+            Status::Skipped | Status::NotDue | Status::Pending => {}
+        }
+    }
+    streak
+}
+
+/// The most due days in a row the habit was ever done, up to today
+pub fn longest_streak(habit: &Habit, today: NaiveDate, week_start: Weekday) -> u32 {
+    let first = match habit.stats.keys().next() {
+        Some(first) => *first,
+        None => return 0,
+    };
+
+    let (mut longest, mut streak) = (0, 0);
+    for status in habit.statuses(first, today, today, week_start) {
+        match status {
+            Status::Done => {
+                streak += 1;
+                longest = longest.max(streak);
+            }
+            Status::Missed => streak = 0,
+            Status::Skipped | Status::NotDue | Status::Pending => {}
+        }
+    }
+    longest
+}
+
+/// Share of the due days between from and to, both included, on which the habit was done
+/// Skipped days, and days that are not due or still pending as of today are left out
+/// Between 0 and 1, or 0 if there are no days to count
+pub fn completion_rate(
+    habit: &Habit,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+    week_start: Weekday,
+) -> f64 {
+    let (mut done, mut total) = (0, 0);
+    for status in habit.statuses(from, to, today, week_start) {
+        match status {
+            Status::Done => {
+                done += 1;
+                total += 1;
+            }
+            Status::Missed => total += 1,
+            Status::Skipped | Status::NotDue | Status::Pending => {}
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        done as f64 / total as f64
+    }
+}
+
+/// Sum of the counts or amounts between from and to, both included
+pub fn total(habit: &Habit, from: NaiveDate, to: NaiveDate) -> f64 {
+    if from > to {
+        return 0.0;
+    }
+    habit
+        .stats
+        .range(from..=to)
+        .filter_map(|(_, entry)| entry.amount())
+        .sum()
+}
+
+/// Sum of the counts or amounts for every week, keyed by the first day of the week,
+/// weeks starting on `week_start`
+pub fn weekly_totals(habit: &Habit, week_start: Weekday) -> BTreeMap<NaiveDate, f64> {
+    totals_by(habit, |day| habit::week_start(day, week_start))
+}
+
+/// Sum of the counts or amounts for every month, keyed by the first day of the month
+pub fn monthly_totals(habit: &Habit) -> BTreeMap<NaiveDate, f64> {
+    totals_by(habit, |day| day.with_day(1).unwrap())
+}
+
+fn totals_by(habit: &Habit, period: impl Fn(NaiveDate) -> NaiveDate) -> BTreeMap<NaiveDate, f64> {
+    let mut totals = BTreeMap::new();
+    for (day, entry) in &habit.stats {
+        if let Some(amount) = entry.amount() {
+            *totals.entry(period(*day)).or_insert(0.0) += amount;
+        }
+    }
+    totals
+}
+
+/// How much the habit was done on the days between from and to, both included, between 0 and 1
+/// Counts and amounts are measured against the goal, or against the largest one in the range
+/// Days without an entry, or that were skipped, are left out
+pub fn intensity(habit: &Habit, from: NaiveDate, to: NaiveDate) -> BTreeMap<NaiveDate, f64> {
+    if from > to {
+        return BTreeMap::new();
+    }
+    let entries = habit.stats.range(from..=to);
+    let scale = match habit.goal.filter(|goal| *goal > 0.0) {
+        Some(goal) => goal,
+        None => entries
+            .clone()
+            .filter_map(|(_, entry)| entry.amount())
+            .fold(0.0, f64::max),
+    };
+    entries
+        .filter_map(|(day, entry)| {
+            let level = match entry.amount() {
+                Some(amount) if scale > 0.0 => (amount / scale).min(1.0),
+                Some(_) => return None,
+                None if entry.is_done(habit.goal) => 1.0,
+                None => return None,
+            };
+            Some((*day, level))
+        })
+        .collect()
+}
+
+/// How often every character was entered up to a day, from a day if given
+pub fn histogram(habit: &Habit, from: Option<NaiveDate>, to: NaiveDate) -> BTreeMap<char, u32> {
+    let mut histogram = BTreeMap::new();
+    let entries = match from {
+        Some(from) if from > to => return histogram,
+        Some(from) => habit.stats.range(from..=to),
+        None => habit.stats.range(..=to),
+    };
+    for (_, entry) in entries {
+        if let Entry::Char(c) = entry {
+            *histogram.entry(*c).or_insert(0) += 1;
+        }
+    }
+    histogram
+}
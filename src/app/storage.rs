@@ -0,0 +1,214 @@
+use std::fs::{self, create_dir_all, File};
+use std::io::{self, prelude::*};
+use std::path::{Path, PathBuf};
+use std::{error, fmt};
+
+use chrono::{Duration, Local, NaiveDateTime};
+use eyre::{eyre, Result};
+
+// TODO: Change this?
+const FILE_NAME: &str = "habit.json";
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "habit-";
+const QUARANTINE_PREFIX: &str = "habit.corrupt-";
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Number of backups kept around before the oldest ones are removed
+pub const MAX_BACKUPS: usize = 10;
+/// Minimum time between two automatic backups, so that autosaving doesn't rotate
+/// out the older backups within a single session
+const BACKUP_INTERVAL_MINUTES: i64 = 60;
+
+/// Errors while loading the state from the disk
+#[derive(Debug)]
+pub enum LoadError {
+    /// The state file exists but could not be read
+    Io { path: PathBuf, source: io::Error },
+    /// The state file was read but is not valid, a copy of it may have been put aside
+    Corrupt {
+        path: PathBuf,
+        quarantine: Option<PathBuf>,
+        source: serde_json::Error,
+    },
+    /// The state file was written by a newer version of rabit
+    Unsupported { path: PathBuf, version: u64 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            LoadError::Corrupt {
+                path,
+                quarantine,
+                source,
+            } => {
+                write!(f, "{} is corrupt: {}", path.display(), source)?;
+                if let Some(quarantine) = quarantine {
+                    write!(f, " (a copy was saved to {})", quarantine.display())?;
+                }
+                Ok(())
+            }
+            LoadError::Unsupported { path, version } => write!(
+                f,
+                "{} was written by a newer version of rabit (schema version {}), please upgrade",
+                path.display(),
+                version
+            ),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Corrupt { source, .. } => Some(source),
+            LoadError::Unsupported { .. } => None,
+        }
+    }
+}
+
+/// The rabit directory inside the user's config directory, created if it doesn't exist
+/// Everything else takes the directory to use, so that it is only looked up here
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = dirs::config_dir()
+        .ok_or_else(|| eyre!("Could not find the config directory"))?
+        .join("rabit");
+    create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Path of the file holding the tracker state in the directory
+pub fn state_path(dir: &Path) -> PathBuf {
+    dir.join(FILE_NAME)
+}
+
+/// A hash of the state file as it is on the disk, None if there is none
+/// It tells whether something else wrote the file since it was last read or written
+pub fn state_fingerprint(dir: &Path) -> Option<u64> {
+    fs::read(state_path(dir))
+        .ok()
+        .map(|data| fingerprint(&data))
+}
+
+/// A hash of the bytes that stays the same between runs, FNV-1a
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn backup_dir(dir: &Path) -> Result<PathBuf> {
+    let dir = dir.join(BACKUP_DIR);
+    create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Write the contents to a temporary file next to the path, and rename it over the
+/// path once it is fully on the disk. Readers either see the old or the new file,
+/// never a half written one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    // The rename is only on the disk once the directory holding the file is
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => sync_dir(dir),
+        _ => sync_dir(Path::new(".")),
+    }
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+// Directories can't be opened like files elsewhere, renames are left to the system
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// All the backups of the state file, the newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_dir(dir)?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| backup_time(path).is_some())
+        .collect();
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// The time a backup was taken, from its file name
+pub fn backup_time(path: &Path) -> Option<NaiveDateTime> {
+    let name = path.file_stem()?.to_str()?;
+    let stamp = name.strip_prefix(BACKUP_PREFIX)?;
+    NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT).ok()
+}
+
+/// Copy the current state file into the backups directory and drop the oldest
+/// backups beyond `MAX_BACKUPS`. Unless forced, nothing is done if the latest
+/// backup is recent enough
+pub fn backup_state(dir: &Path, force: bool) -> Result<()> {
+    let path = state_path(dir);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let now = Local::now().naive_local();
+    let backups = list_backups(dir)?;
+    if !force {
+        let latest = backups.first().and_then(|b| backup_time(b));
+        if let Some(latest) = latest {
+            if now - latest < Duration::minutes(BACKUP_INTERVAL_MINUTES) {
+                return Ok(());
+            }
+        }
+    }
+
+    let name = format!("{}{}.json", BACKUP_PREFIX, now.format(BACKUP_TIME_FORMAT));
+    fs::copy(&path, backup_dir(dir)?.join(name))?;
+
+    for old in list_backups(dir)?.iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Copy a state file which could not be parsed next to it, so that it can be
+/// recovered by hand whatever happens to the original afterwards
+/// A copy is only made once, loading the same file again returns the copy made before
+pub fn quarantine(path: &Path) -> Result<PathBuf> {
+    let contents = fs::read(path)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let copied = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(QUARANTINE_PREFIX))
+        })
+        .find(|p| fs::read(p).is_ok_and(|c| c == contents));
+    if let Some(copied) = copied {
+        return Ok(copied);
+    }
+
+    let name = format!(
+        "{}{}.json",
+        QUARANTINE_PREFIX,
+        Local::now().format(BACKUP_TIME_FORMAT)
+    );
+    let quarantine = path.with_file_name(name);
+    fs::copy(path, &quarantine)?;
+    Ok(quarantine)
+}
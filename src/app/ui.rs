@@ -1,16 +1,39 @@
-use chrono::{Datelike, Utc};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    text::Text,
-    widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table},
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, Wrap},
     Frame,
 };
 
-use super::App;
+use std::ops::Range;
 
-const TABLE_WIDTH: u16 = 39;
+use super::{
+    habit::{compact, week_start, HabitType},
+    keymap::Action,
+    schedule::Status,
+    stats::{self, Stats, RATE_DAYS},
+    App, Line, View,
+};
+
+// The margins on both sides of the grid
+const MARGIN: u16 = 4;
+// Seven days of three columns each, as in the calendar
+const DAYS_WIDTH: u16 = 21;
+// The title and the two lines of the header of the table, then the status, command and help bars
+const CHROME: u16 = 7;
+// The label column fits the longest name within these, longer ones are cut short
+const MIN_LABEL_WIDTH: u16 = 10;
+const MAX_LABEL_WIDTH: u16 = 32;
+const STATS_WIDTH: u16 = 24;
+// The margins, the weekday labels and the up to 54 weeks a year touches
+const YEAR_WIDTH: u16 = 65;
+// The month names, the days of the week, a blank line and the legend
+const YEAR_ROWS: u16 = 10;
+// Enough for the longest stats panel, an ALPHA habit with a full histogram
+const STATS_HEIGHT: u16 = 15;
 const MONTHS: [&str; 12] = [
     "January",
     "February",
@@ -25,39 +48,449 @@ const MONTHS: [&str; 12] = [
     "November",
     "December",
 ];
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// The short name of the weekday some days after `first`
+fn weekday_name(first: Weekday, days: u32) -> &'static str {
+    WEEKDAYS[((first.num_days_from_monday() + days) % 7) as usize]
+}
+// Room for the keys and the description of every action
+const HELP_WIDTH: u16 = 46;
+// A month spans at most six weeks
+const MONTH_ROWS: u16 = 6;
 
-/// Split the terminal area into major chunks such that the UI is always centered Calculate the length and the width of the tracker contents to ensure the content is centered
-pub fn split_area<B>(f: &mut Frame<B>, habit_count: &u16) -> Rect
+/// Center an area of the required size in the terminal, as much of it as fits
+pub fn split_area<B>(f: &mut Frame<B>, required_height: u16, required_width: u16) -> Rect
 where
     B: Backend,
 {
-    let total_height = f.size().height;
-    let required_height = habit_count + 9;
-    let empty_v_space = total_height - required_height;
-
-    let total_width = f.size().width;
-    let required_width = TABLE_WIDTH;
-    let empty_h_space = total_width - TABLE_WIDTH;
-
-    let h_constraints = [
-        Constraint::Length(empty_h_space / 2),
-        Constraint::Length(required_width),
-        Constraint::Length(empty_h_space / 2),
-    ];
-    let v_constraints = [
-        Constraint::Length(empty_v_space / 2),
-        Constraint::Length(required_height),
-        Constraint::Length(empty_v_space / 2),
+    let size = f.size();
+    let (width, height) = (
+        required_width.min(size.width),
+        required_height.min(size.height),
+    );
+    Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Fit - How much of the grid fits in the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fit {
+    pub margin: u16,
+    pub vertical_margin: u16,
+    pub label_width: u16,
+    pub stats: bool,
+    /// Number of days shown, the others are scrolled to
+    pub days: u16,
+    /// Number of lines of the table shown, the others are scrolled to
+    pub rows: u16,
+}
+
+/// Fit a grid of `row_count` lines and `day_count` days, with labels up to `longest` wide,
+/// in the terminal
+/// The stats panel goes first, then the margins, then the days and the lines that don't fit
+/// None if not even one day of one habit fits
+pub fn fit(size: Rect, view: View, row_count: u16, day_count: u16, longest: u16) -> Option<Fit> {
+    let label_width = longest.clamp(MIN_LABEL_WIDTH, MAX_LABEL_WIDTH);
+    // The heatmap has no labels, and is cut short rather than left out
+    let table_width = match view {
+        View::Year => YEAR_WIDTH,
+        _ => 2 * MARGIN + label_width + 3 * day_count,
+    };
+    let stats = view != View::Year && size.width >= table_width + STATS_WIDTH;
+    let room = match stats {
+        true => size.width - STATS_WIDTH,
+        false => size.width,
+    };
+    let margin = if room >= table_width { MARGIN } else { 1 };
+    let label_width = label_width
+        .min(room.saturating_sub(2 * margin + 3 * day_count))
+        .max(MIN_LABEL_WIDTH);
+    let days = match view {
+        View::Year => day_count,
+        _ => (room.saturating_sub(2 * margin + label_width) / 3).min(day_count),
+    };
+
+    let vertical_margin = if size.height >= row_count + CHROME + 4 {
+        2
+    } else {
+        0
+    };
+    let rows = row_count.min(size.height.saturating_sub(CHROME + 2 * vertical_margin));
+    if days == 0 || rows < row_count.min(1) || size.height < CHROME {
+        return None;
+    }
+    Some(Fit {
+        margin,
+        vertical_margin,
+        label_width,
+        stats,
+        days,
+        rows,
+    })
+}
+
+/// The text cut to a number of characters, ending with an ellipsis if it was longer
+pub fn ellipsize(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_owned();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// The months in view, both of them when a week spans two months
+fn title(app: &App) -> String {
+    let name = |day: NaiveDate| MONTHS[day.month0() as usize];
+    let dates = app.tracker.get_date_range();
+    let (first, last) = (dates[0], dates[dates.len() - 1]);
+    match app.view {
+        View::Month { .. } => {
+            let day = app.state.selected().map_or(first, |(_, col)| dates[col]);
+            format!("{} {}", name(day), day.year())
+        }
+        View::Year => {
+            let (_, col) = app.state.selected().unwrap_or((0, 0));
+            match app.selected_habit() {
+                Some(i) => format!("{} {}", app.tracker.habits[i].label, dates[col].year()),
+                None => dates[col].year().to_string(),
+            }
+        }
+        View::Archive => String::from("Archive"),
+        View::Week if first.month() == last.month() => name(first).to_owned(),
+        View::Week if first.year() == last.year() => format!("{} / {}", name(first), name(last)),
+        View::Week => format!(
+            "{} {} / {} {}",
+            name(first),
+            first.year(),
+            name(last),
+            last.year()
+        ),
+    }
+}
+
+/// Draw a calendar of the month of the selected day
+/// Days are colored by the status of the selected habit, or by how many of all habits were done
+fn draw_month<B>(f: &mut Frame<B>, app: &App, area: Rect, all: bool)
+where
+    B: Backend,
+{
+    let (row, col) = match (app.selected_habit(), app.state.selected()) {
+        (Some(row), Some((_, col))) => (row, col),
+        _ => return,
+    };
+    let selected = app.tracker.get_date_range()[col];
+    let today = app.tracker.today();
+    let first = selected.with_day(1).unwrap();
+    let last = (first + Duration::days(31)).with_day(1).unwrap() - Duration::days(1);
+    let theme = &app.config.theme;
+
+    // The statuses of the days of the month, for every habit that is colored by them
+    let habits = if all { app.tracker.active() } else { vec![row] };
+    let month: Vec<Vec<Status>> = habits
+        .iter()
+        .map(|&i| app.tracker.habits[i].statuses(first, last, today, app.tracker.grid.week_start))
+        .collect();
+    let color = |day: NaiveDate| {
+        let d = (day - first).num_days() as usize;
+        if all {
+            let statuses: Vec<Status> = month.iter().map(|statuses| statuses[d]).collect();
+            let count = |status| statuses.iter().filter(|s| **s == status).count();
+            let (done, due) = (
+                count(Status::Done),
+                count(Status::Done) + count(Status::Missed) + count(Status::Pending),
+            );
+            return if due == 0 {
+                theme.muted
+            } else if done == due {
+                theme.done
+            } else if done > 0 {
+                theme.partial
+            } else if count(Status::Missed) > 0 {
+                theme.missed
+            } else {
+                theme.text
+            };
+        }
+        let habit = &app.tracker.habits[row];
+        match month[0][d] {
+            Status::Done => theme.done,
+            _ if habit.progress(day).is_some() => theme.partial,
+            Status::Missed => theme.missed,
+            Status::Pending => theme.text,
+            Status::Skipped | Status::NotDue => theme.muted,
+        }
+    };
+
+    let first_weekday = app.tracker.grid.week_start;
+    let header = Row::new((0..7).map(|i| {
+        Cell::from(format!("{:^3}", weekday_name(first_weekday, i)))
+            .style(Style::default().fg(theme.accent))
+    }));
+    let mut weeks = vec![];
+    let mut week = week_start(first, first_weekday);
+    while week < first || week.month() == first.month() {
+        let cells = (0..7).map(|i| {
+            let day = week + Duration::days(i);
+            if day.month() != first.month() {
+                return Cell::from("");
+            }
+            let mut style = Style::default().fg(color(day));
+            if day == selected {
+                style = style.bg(theme.selected_bg);
+            } else if day == today {
+                style = style.bg(theme.muted);
+            }
+            Cell::from(format!("{:^3}", day.day())).style(style)
+        });
+        weeks.push(Row::new(cells));
+        week += Duration::days(7);
+    }
+
+    let calendar = Table::new(weeks)
+        .header(header)
+        .widths(&[Constraint::Length(3); 7])
+        .column_spacing(0);
+    f.render_widget(calendar, area);
+}
+
+/// Draw a heatmap of the year of the selected day for the selected habit, a column per week
+/// Days are shaded by how much the habit was done
+fn draw_year<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let (row, col) = match (app.selected_habit(), app.state.selected()) {
+        (Some(row), Some((_, col))) => (row, col),
+        _ => return,
+    };
+    let habit = &app.tracker.habits[row];
+    let selected = app.tracker.get_date_range()[col];
+    let today = app.tracker.today();
+    let year = selected.year();
+    let (first, last) = (
+        NaiveDate::from_ymd(year, 1, 1),
+        NaiveDate::from_ymd(year, 12, 31),
+    );
+    let levels = stats::intensity(habit, first, last);
+    let theme = &app.config.theme;
+
+    let first_weekday = app.tracker.grid.week_start;
+    let mut weeks = vec![];
+    let mut week = week_start(first, first_weekday);
+    while week <= last {
+        weeks.push(week);
+        week += Duration::days(7);
+    }
+
+    // The name of every month goes over the week it starts in
+    let mut months = vec![' '; weeks.len()];
+    for (w, week) in weeks.iter().enumerate() {
+        let end = *week + Duration::days(6);
+        if end.day() <= 7 && end.year() == year && w + 3 <= months.len() {
+            let name = MONTHS[end.month0() as usize].chars().take(3);
+            months.splice(w..w + 3, name);
+        }
+    }
+    let mut lines = vec![Spans::from(format!(
+        "   {}",
+        months.into_iter().collect::<String>()
+    ))];
+
+    for d in 0..7 {
+        let label = match d {
+            0 | 2 | 4 => weekday_name(first_weekday, d),
+            _ => "",
+        };
+        let mut spans = vec![Span::styled(
+            format!("{:<3}", label),
+            Style::default().fg(theme.accent),
+        )];
+        for week in &weeks {
+            let day = *week + Duration::days(d as i64);
+            let (symbol, color) = if day.year() != year || day > today {
+                (" ", Color::Reset)
+            } else {
+                match levels.get(&day) {
+                    Some(level) if *level > 0.75 => ("█", theme.done),
+                    Some(level) if *level > 0.5 => ("▓", theme.done),
+                    Some(level) if *level > 0.25 => ("▒", theme.done),
+                    Some(_) => ("░", theme.done),
+                    None => ("·", theme.muted),
+                }
+            };
+            let mut style = Style::default().fg(color);
+            if day == selected {
+                style = style.bg(theme.selected_bg);
+            }
+            spans.push(Span::styled(symbol, style));
+        }
+        lines.push(Spans::from(spans));
+    }
+
+    let done = habit
+        .stats
+        .range(first..=last)
+        .filter(|(_, entry)| entry.is_done(habit.goal))
+        .count();
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(vec![
+        Span::raw("   Less "),
+        Span::styled("░▒▓█", Style::default().fg(theme.done)),
+        Span::raw(format!(
+            " More    {} {} done in {}",
+            done,
+            if done == 1 { "day" } else { "days" },
+            year
+        )),
+    ]));
+
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// Draw when the archived habits were archived and how many days they were done
+/// The rest of their stats are in the side panel
+fn draw_archive<B>(f: &mut Frame<B>, app: &App, area: Rect, shown: Range<usize>)
+where
+    B: Backend,
+{
+    let theme = &app.config.theme;
+    let selected = app.state.selected().map(|(row, _)| row);
+    let rows: Vec<Row> = app
+        .rows()
+        .iter()
+        .enumerate()
+        .skip(shown.start)
+        .take(shown.len())
+        .map(|(row, &i)| {
+            let habit = &app.tracker.habits[i];
+            let done = habit
+                .stats
+                .values()
+                .filter(|entry| entry.is_done(habit.goal))
+                .count();
+            let archived = habit
+                .archived
+                .map(|day| day.to_string())
+                .unwrap_or_default();
+            let style = match selected == Some(row) {
+                true => Style::default().fg(theme.selected_fg).bg(theme.selected_bg),
+                false => Style::default().fg(theme.text),
+            };
+            Row::new([Cell::from(archived), Cell::from(format!("{:>6}", done))]).style(style)
+        })
+        .collect();
+    let empty = rows.is_empty();
+    // As high as the header of the grid, for the rows to line up with the names
+    let header = Row::new(["Archived", "  Done"])
+        .height(2)
+        .style(Style::default().fg(theme.accent));
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&[Constraint::Length(11), Constraint::Length(6)])
+        .column_spacing(1);
+    f.render_widget(table, area);
+    if empty {
+        let hint =
+            Paragraph::new(Text::from("Nothing archived")).style(Style::default().fg(theme.muted));
+        f.render_widget(
+            hint,
+            Rect {
+                y: area.y + 1,
+                height: 1,
+                ..area
+            },
+        );
+    }
+}
+
+/// Draw the statistics of the selected habit in a side panel
+fn draw_stats<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunk = Layout::default()
+        .constraints([Constraint::Min(0)])
+        .vertical_margin(2)
+        .split(area)[0];
+    let block = Block::default()
+        .title("Stats")
+        .borders(Borders::LEFT)
+        .style(Style::default().fg(app.config.theme.text));
+
+    let habit = match app.selected_habit() {
+        Some(i) => &app.tracker.habits[i],
+        None => {
+            let hint = Paragraph::new(Text::from(" Select a habit")).block(block);
+            f.render_widget(hint, chunk);
+            return;
+        }
+    };
+
+    let stats = Stats::new(habit, app.tracker.today(), app.tracker.grid.week_start);
+    let mut lines = vec![
+        format!(" {}", ellipsize(&habit.label, STATS_WIDTH as usize - 3)),
+        format!(" {}", habit.schedule),
+        format!(" Streak        {:>6}", stats.current_streak),
+        format!(" Best streak   {:>6}", stats.longest_streak),
+        format!(
+            " Last {} days  {:>5.0}%",
+            RATE_DAYS,
+            stats.completion_rate * 100.0
+        ),
     ];
-    let sub = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(h_constraints.clone().as_ref())
-        .split(f.size());
-    let main = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(v_constraints.clone().as_ref())
-        .split(sub[1]);
-    return main[1];
+    match habit.habit_type {
+        HabitType::COUNT | HabitType::NUMBER => {
+            lines.push(format!(" This week     {:>6}", compact(stats.week_total)));
+            lines.push(format!(" This month    {:>6}", compact(stats.month_total)));
+
+            // Progress of the selected day toward the goal
+            if let (Some(goal), Some((_, col))) = (habit.goal, app.state.selected()) {
+                let date = app.tracker.get_date_range()[col];
+                let amount = habit
+                    .stats
+                    .get(&date)
+                    .and_then(|e| e.amount())
+                    .unwrap_or(0.0);
+                let filled = (habit.progress(date).unwrap_or(0.0) * 10.0).round() as usize;
+                lines.push(String::new());
+                lines.push(format!(
+                    " Goal {} {}",
+                    goal,
+                    habit.unit.as_deref().unwrap_or_default()
+                ));
+                lines.push(format!(
+                    " {}{} {}/{}",
+                    "█".repeat(filled),
+                    "░".repeat(10 - filled),
+                    compact(amount),
+                    compact(goal)
+                ));
+            }
+        }
+        HabitType::ALPHA => {
+            lines.push(String::new());
+            for (c, n) in stats.histogram.iter().take(5) {
+                lines.push(format!(
+                    " {}  {:<10} {:>4}",
+                    c,
+                    "▇".repeat((*n).min(10) as usize),
+                    n
+                ));
+            }
+        }
+        HabitType::BIT => {}
+    }
+
+    let panel = Paragraph::new(Text::from(lines.join("\n")))
+        .style(Style::default().fg(app.config.theme.accent))
+        .block(block);
+    f.render_widget(panel, chunk);
 }
 
 /// The main UI function to draw the table
@@ -65,11 +498,114 @@ pub fn draw<B>(f: &mut Frame<B>, app: &mut App)
 where
     B: Backend,
 {
-    let habit_count = app.tracker.habits.len() as u16; // Habit count
-    let layout = split_area(f, &habit_count); // Main Layout
+    let lines = app.lines();
+    let dates = app.tracker.get_date_range();
+    // The calendar and the archive take the room of a week
+    let day_count = match app.view {
+        View::Week => dates.len() as u16,
+        _ => 7,
+    };
+    // The row of the selection each habit line is, group headers can't be selected
+    let mut next_row = 0;
+    let line_rows: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| match line {
+            Line::Habit(_) => {
+                next_row += 1;
+                Some(next_row - 1)
+            }
+            Line::Group { .. } => None,
+        })
+        .collect();
+    let line_count = lines.len() as u16; // Habits and group headers
+    let row_count = match app.view {
+        View::Week => line_count,
+        View::Archive => line_count.max(1),
+        View::Month { .. } => line_count.max(MONTH_ROWS),
+        View::Year => YEAR_ROWS,
+    };
+    // The names of the habits, and the headers of their groups
+    let labels: Vec<(Option<usize>, String)> = lines
+        .iter()
+        .zip(&line_rows)
+        .map(|(line, &row)| match line {
+            Line::Habit(i) => (row, app.tracker.habits[*i].label.to_owned()),
+            Line::Group {
+                name,
+                folded: false,
+                ..
+            } => (None, format!("▾ {}", name)),
+            Line::Group {
+                name,
+                folded: true,
+                count,
+            } => (None, format!("▸ {} ({})", name, count)),
+        })
+        .collect();
+
+    // The label column fits the longest name, as far as the terminal leaves room for it
+    let longest = labels
+        .iter()
+        .map(|(_, label)| label.chars().count() as u16 + 1)
+        .max()
+        .unwrap_or(0);
+    let Fit {
+        margin,
+        vertical_margin,
+        label_width,
+        stats,
+        days,
+        rows,
+    } = match fit(f.size(), app.view, row_count, day_count, longest) {
+        Some(fit) => fit,
+        None => {
+            let notice = Paragraph::new(Text::from("Terminal too small"))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            f.render_widget(notice, f.size());
+            return;
+        }
+    };
+
+    // The heatmap is too wide to leave room for the stats panel
+    let table_width = match app.view {
+        View::Year => YEAR_WIDTH,
+        _ => 2 * margin + label_width + 3 * days,
+    };
+    let stats_width = if stats { STATS_WIDTH } else { 0 };
+    let height = (rows + CHROME + 2 * vertical_margin).max(if stats { STATS_HEIGHT } else { 0 });
+    let area = split_area(f, height, table_width + stats_width); // Main Layout
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(table_width),
+            Constraint::Length(stats_width),
+        ])
+        .split(area);
+    let (layout, stats_area) = (columns[0], columns[1]);
+    if stats {
+        draw_stats(f, app, stats_area);
+    }
+
+    // The lines that fit, keeping the selected habit in view
+    let selected_row = app.state.selected().map(|(row, _)| row);
+    let selected_line = line_rows
+        .iter()
+        .position(|row| row.is_some() && *row == selected_row);
+    let offset = app
+        .state
+        .scroll_to(selected_line, rows as usize, lines.len());
+    let shown = offset..(offset + rows as usize).min(lines.len()).max(offset);
+    // The days that fit, keeping the selected one in view
+    let selected_col = app.state.selected().map_or(0, |(_, col)| col);
+    let first_day = (selected_col + 1).saturating_sub(days as usize);
+    let shown_days = first_day..(first_day + days as usize).min(dates.len());
+
+    let theme = app.config.theme.clone();
+    // Without a margin there is no room for the title
     let bg_block = Block::default()
-        .title("My Habits")
-        .style(Style::default().fg(Color::White))
+        .title(if vertical_margin > 0 { "My Habits" } else { "" })
+        .style(Style::default().fg(theme.text))
         .title_alignment(Alignment::Center);
     f.render_widget(bg_block, layout); // Render the title
 
@@ -78,101 +614,129 @@ where
         .direction(Direction::Vertical)
         .constraints(
             [
-                Constraint::Length(app.tracker.habits.len() as u16 + 3), // Main Table
-                Constraint::Length(1),                                   // Command Bar
-                Constraint::Length(1),                                   // Help Bar
+                Constraint::Length(rows + 4), // Main Table
+                Constraint::Length(1),        // Status Line
+                Constraint::Length(1),        // Command Bar
+                Constraint::Length(1),        // Help Bar
             ]
             .as_ref(),
         )
-        .horizontal_margin(4)
-        .vertical_margin(2)
+        .horizontal_margin(margin)
+        .vertical_margin(vertical_margin)
         .split(layout);
 
     // Rename
-    let (top_chunk, cl_chunk, help_chunk) = (main_chunk[0], main_chunk[1], main_chunk[2]);
+    let (top_chunk, status_chunk, cl_chunk, help_chunk) =
+        (main_chunk[0], main_chunk[1], main_chunk[2], main_chunk[3]);
 
     let inner_table_chunk = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Length(habit_count)])
+        .constraints([Constraint::Length(2), Constraint::Length(rows + 2)])
         .split(top_chunk);
 
     let (heading_chunk, table_chunk) = (inner_table_chunk[0], inner_table_chunk[1]);
 
     // Current month for tracking
-    let month = MONTHS[app.tracker.start_date.month() as usize - 1].to_owned();
-
-    let title = Paragraph::new(Text::from(month)).alignment(Alignment::Left);
+    let title = Paragraph::new(Text::from(title(app))).alignment(Alignment::Left);
     f.render_widget(title, heading_chunk);
 
     let table_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(10), Constraint::Max(3 * 7)].as_ref())
+        .constraints([Constraint::Length(label_width), Constraint::Max(DAYS_WIDTH)].as_ref())
         .split(table_chunk);
     let (label_chunk, values_chunk) = (table_chunks[0], table_chunks[1]);
 
     // Cell Styles
-    let cell_normal_style = Style::default().fg(Color::White);
-    let cell_selected_style = Style::default().fg(Color::Magenta).bg(Color::White);
-    let cell_disabled_style = Style::default().bg(Color::DarkGray);
+    let cell_normal_style = Style::default().fg(theme.text);
+    let cell_selected_style = Style::default().fg(theme.selected_fg).bg(theme.selected_bg);
+    let cell_disabled_style = Style::default().bg(theme.muted);
     let header_labels = app.tracker.get_header_labels();
-    let header_cells = header_labels.iter().map(|h| {
-        return Cell::from(h.to_owned()).style(Style::default().fg(Color::LightMagenta));
+    let header_cells = shown_days.clone().map(|j| {
+        let weekday = format!("{:^3}", weekday_name(dates[j].weekday(), 0));
+        Cell::from(format!("{}\n{}", weekday, header_labels[j]))
+            .style(Style::default().fg(theme.accent))
     });
-    let header = Row::new(header_cells).height(1);
+    let header = Row::new(header_cells).height(2);
     let column_constraint = Constraint::Length(3);
-    let column_width = &[column_constraint; 7];
+    let column_width = vec![column_constraint; shown_days.len()];
 
-    // All the tracked data
+    // All the tracked data, of the habits in the lines, with nothing by the group headers
     let values = app.tracker.values_v2();
-    let value_rows = values.iter().enumerate().map(|(i, row)| {
-        let cells = row.iter().enumerate().map(|(j, stat)| {
-            let (a, b) = match app.state.selected() {
-                Some((x, y)) => (x, y),
-                None => (0, 0),
+    let today = app.tracker.today();
+    let value_rows = lines[shown.clone()]
+        .iter()
+        .zip(&line_rows[shown.clone()])
+        .map(|(line, row)| {
+            let (habit_index, i) = match (line, row) {
+                (Line::Habit(habit_index), Some(i)) => (*habit_index, *i),
+                _ => return Row::new(Vec::<Cell>::new()),
             };
+            let habit = &app.tracker.habits[habit_index];
+            let statuses = habit.statuses(
+                dates[0],
+                dates[dates.len() - 1],
+                today,
+                app.tracker.grid.week_start,
+            );
+            let cells = values[habit_index][shown_days.clone()]
+                .iter()
+                .enumerate()
+                .map(|(j, stat)| {
+                    let j = first_day + j;
+                    let (a, b) = match app.state.selected() {
+                        Some((x, y)) => (x, y),
+                        None => (0, 0),
+                    };
 
-            let mut cell_style = cell_normal_style;
-            let now = Utc::now().day().to_string();
-            if (i, j) == (a, b) {
-                if header_labels[j] == now {
-                    cell_style = cell_selected_style
-                } else {
-                    cell_style = cell_disabled_style
-                }
-            } else if header_labels[j] == now {
-                cell_style = cell_disabled_style
-            }
+                    let mut cell_style = cell_normal_style;
+                    let is_today = dates[j] == today;
+                    if (i, j) == (a, b) {
+                        if app.can_edit(dates[j]).is_ok() {
+                            cell_style = cell_selected_style
+                        } else {
+                            cell_style = cell_disabled_style
+                        }
+                    } else if is_today {
+                        cell_style = cell_disabled_style
+                    }
 
-            let text = match &*stat {
-                None => String::from(" ◦ "),
-                // Some(s) => format!(" {} ", s).to_string()
-                Some(s) => match s.as_str() {
-                    "true" => String::from(" • "),
-                    s => format!(" {} ", s).to_string(),
-                },
-            };
+                    // Empty days the schedule doesn't ask for are not missed
+                    let not_due = statuses[j] == Status::NotDue;
+                    let noted = habit.notes.contains_key(&dates[j]);
+                    let text =
+                        app.config
+                            .glyphs
+                            .of(&habit.habit_type)
+                            .cell(stat.as_ref(), noted, not_due);
 
-            let fg_color = match &*stat {
-                None => Color::Red,
-                Some(_) => Color::DarkGray,
-            };
+                    // Counts and amounts are colored by how close they are to the goal
+                    let fg_color = match stat {
+                        None if not_due || noted => theme.muted,
+                        None => theme.missed,
+                        Some(_) => match habit.progress(dates[j]) {
+                            Some(p) if p >= 1.0 => theme.done,
+                            Some(_) => theme.partial,
+                            None => theme.muted,
+                        },
+                    };
 
-            Cell::from(text).style(cell_style.fg(fg_color))
+                    Cell::from(text).style(cell_style.fg(fg_color))
+                });
+            Row::new(cells)
         });
-        Row::new(cells)
-    });
 
     // The table with the boolean values
     let values_table = Table::new(value_rows)
         .header(header)
-        .widths(column_width)
+        .widths(&column_width)
         .column_spacing(0);
 
-    f.render_widget(values_table, values_chunk);
-
-    // Table for the name of the habit
-
-    let labels = app.tracker.labels();
+    match app.view {
+        View::Week => f.render_widget(values_table, values_chunk),
+        View::Month { all } => draw_month(f, app, values_chunk, all),
+        View::Year => draw_year(f, app, table_chunk),
+        View::Archive => draw_archive(f, app, values_chunk, shown.clone()),
+    }
 
     // Was using tables before lists
     // let habit_rows = labels.iter().map(|h| {
@@ -183,38 +747,153 @@ where
     //     .header(Row::new([Cell::from("Habits")]))
     //     .widths([Constraint::Length(10)].as_ref());
 
-    let mut habit_list_items: Vec<ListItem> = labels
+    let view = app.view;
+    let (selected_fg, selected_bg) = (theme.selected_fg, theme.selected_bg);
+    let mut habit_list_items: Vec<ListItem> = labels[shown]
         .iter()
-        .enumerate()
-        .map(move |(i, habit)| {
-            ListItem::new(Text::from(
-                [i.to_string().as_str(), habit.as_str()].join(" "),
-            ))
+        .map(move |(row, label)| {
+            let item = ListItem::new(Text::from(ellipsize(label, label_width as usize - 1)));
+            // The month view is of the selected habit, or of all of them
+            match view {
+                View::Month { all } if row.is_some() && (all || *row == selected_row) => {
+                    item.style(Style::default().fg(selected_fg).bg(selected_bg))
+                }
+                View::Archive if row.is_some() && *row == selected_row => {
+                    item.style(Style::default().fg(selected_fg).bg(selected_bg))
+                }
+                _ => item,
+            }
         })
         .collect();
-    habit_list_items.insert(0, ListItem::new(Text::from(" ")));
-    let habit_list = List::new(habit_list_items).style(Style::default().fg(Color::LightMagenta));
+    // Room for the two lines of the header
+    habit_list_items.splice(0..0, [ListItem::new(" "), ListItem::new(" ")]);
+    let habit_list = List::new(habit_list_items).style(Style::default().fg(theme.accent));
+
+    if app.view != View::Year {
+        f.render_widget(habit_list, label_chunk);
+    }
+
+    // A scrollbar right of the grid, and which lines are in view, when they don't all fit
+    if app.view != View::Year && lines.len() > rows as usize {
+        let (page, total) = (rows as usize, lines.len());
+        let thumb = (page * page / total).max(1);
+        let start = offset * (page - thumb) / (total - page);
+        let bar: Vec<Spans> = (0..page)
+            .map(|i| match (start..start + thumb).contains(&i) {
+                true => Spans::from(Span::styled("┃", Style::default().fg(theme.accent))),
+                false => Spans::from(Span::styled("│", Style::default().fg(theme.muted))),
+            })
+            .collect();
+        let bar_area = Rect::new(values_chunk.right(), table_chunk.y + 2, 1, rows);
+        f.render_widget(Paragraph::new(bar), bar_area.intersection(layout));
+
+        let position = Paragraph::new(Text::from(format!(
+            "{}-{}/{}",
+            offset + 1,
+            offset + page,
+            total
+        )))
+        .style(Style::default().fg(theme.muted))
+        .alignment(Alignment::Right);
+        let position_area = Rect::new(heading_chunk.x, heading_chunk.y + 1, heading_chunk.width, 1);
+        f.render_widget(position, position_area.intersection(heading_chunk));
+    }
 
-    f.render_widget(habit_list, label_chunk);
+    // The whole name of the selected habit, which may be cut short in the grid
+    if let Some(i) = app.selected_habit() {
+        let habit = &app.tracker.habits[i];
+        let status = match &habit.group {
+            Some(group) => format!("{} · {}", habit.label, group),
+            None => habit.label.to_owned(),
+        };
+        let status = Paragraph::new(Text::from(ellipsize(&status, status_chunk.width as usize)))
+            .style(Style::default().fg(theme.text));
+        f.render_widget(status, status_chunk);
+    }
 
     // -----
 
-    let command_bg = Block::default().style(Style::default().bg(Color::DarkGray));
+    let command_bg = Block::default().style(Style::default().bg(theme.muted));
 
     let command = Paragraph::new(Text::from([":".to_owned(), app.input.to_owned()].join(" ")))
         .alignment(Alignment::Left)
         .block(command_bg);
     f.render_widget(command, cl_chunk);
+    if matches!(app.mode, super::AppMode::COMMAND) {
+        let typed = app.input.get(..app.prompt.cursor()).unwrap_or_default();
+        let x = cl_chunk.x + 2 + typed.chars().count() as u16;
+        f.set_cursor(x.min(cl_chunk.right().saturating_sub(1)), cl_chunk.y);
+    }
 
     let mode = match app.mode {
         super::AppMode::NORMAL => "NORMAL Mode",
         super::AppMode::COMMAND => "COMMAND Mode",
         super::AppMode::HABIT => "HABIT mode",
+        super::AppMode::CONFIRM => "CONFIRM Mode",
+    };
+
+    // Show the keys of an unfinished sequence, or the note left on the selected day, if any
+    let bindings = app.keymap.bindings();
+    let help_key = bindings
+        .iter()
+        .find(|(action, sequences)| *action == Action::Help && !sequences.is_empty())
+        .map(|(_, sequences)| sequences[0].to_owned());
+    let pending = app.keymap.pending();
+    let note = match (app.selected_habit(), app.state.selected()) {
+        (Some(i), Some((_, col))) => app.tracker.habits[i].notes.get(&dates[col]),
+        _ => None,
+    };
+    let hint = match note {
+        _ if !pending.is_empty() => pending,
+        Some(note) => note.to_owned(),
+        _ => match help_key {
+            Some(key) => format!("'{}' for help", key),
+            None => "'q' to quit".to_owned(),
+        },
     };
+    let mut parts = vec![mode.to_owned()];
+    if app.backfill {
+        parts.push(String::from("BACKFILL"));
+    }
+    parts.push(hint);
+    let text = Paragraph::new(Text::from(parts.join(" | "))).alignment(Alignment::Center);
+    f.render_widget(text, help_chunk);
 
-    let text = Paragraph::new(Text::from(
-        [mode.to_owned(), "'q' to quit".to_owned()].join(" | "),
-    ))
-    .alignment(Alignment::Center);
-    f.render_widget(text, help_chunk)
+    if app.show_help {
+        draw_help(f, app);
+    }
+}
+
+/// Draw the key bindings over the middle of the screen
+fn draw_help<B>(f: &mut Frame<B>, app: &App)
+where
+    B: Backend,
+{
+    let lines: Vec<String> = app
+        .keymap
+        .bindings()
+        .iter()
+        .map(|(action, sequences)| format!(" {:<14} {}", sequences.join(" "), action.description()))
+        .collect();
+    let size = f.size();
+    let (width, height) = (
+        HELP_WIDTH.min(size.width),
+        (lines.len() as u16 + 2).min(size.height),
+    );
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let help = Paragraph::new(Text::from(lines.join("\n")))
+        .style(Style::default().fg(app.config.theme.accent))
+        .block(
+            Block::default()
+                .title("Keys")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(app.config.theme.text)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(help, area);
 }
@@ -0,0 +1,507 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use eyre::{bail, eyre, Result};
+
+use crate::app::config::{Config, Editing, Glyphs, Grid};
+use crate::app::habit::{parse_goal, Entry, HabitTracker, HabitType};
+use crate::app::history::History;
+use crate::app::schedule::Status;
+use crate::app::stats::{Stats, RATE_DAYS};
+use crate::app::storage::{self, LoadError};
+
+const USAGE: &str = "Usage:
+    rabit                                              Open the habit grid
+    rabit list                                         List all habits
+    rabit add <HABIT_NAME> [--type bit|count|alpha|number] [--schedule SCHEDULE]
+              [--goal GOAL] [--unit UNIT] [--group G]  Add a habit
+    rabit delete <HABIT> [--yes]                       Delete a habit, --yes to not be asked
+    rabit archive <HABIT>                              Hide a habit from the grid, keeping its stats
+    rabit unarchive <HABIT>                            Bring an archived habit back
+    rabit schedule <HABIT> <SCHEDULE>                  Change the days a habit is due on
+    rabit goal <HABIT> <GOAL> [--unit UNIT]            Set the daily goal of a habit, 0 to remove it
+    rabit mark <HABIT> [--date YYYY-MM-DD] [--value X] Mark a habit for a day
+    rabit show [--week]                                Print the days of the grid, --week for the whole week
+    rabit stats <HABIT>                                Print the streaks and totals of a habit
+    rabit undo                                         Undo the last change to the habits
+    rabit redo                                         Redo the last undone change
+    rabit backups                                      List the backups of the habits
+    rabit restore [BACKUP_NUMBER] [--yes]              Restore a backup, 0 being the latest
+
+<HABIT> is the ID shown by `rabit list`, the habit name or a unique start of either
+<SCHEDULE> is one of `daily`, `mon,wed,fri`, `every:3` (every 3 days from today),
+`3/week` or `10/month`";
+
+/// Subcommands that can be run without opening the UI
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subcommand {
+    List,
+    Add {
+        label: String,
+        habit_type: HabitType,
+        schedule: Option<String>,
+        goal: Option<f64>,
+        unit: Option<String>,
+        group: Option<String>,
+    },
+    Goal {
+        habit: String,
+        goal: f64,
+        unit: Option<String>,
+    },
+    Schedule {
+        habit: String,
+        rule: String,
+    },
+    Delete {
+        habit: String,
+        yes: bool,
+    },
+    Archive {
+        habit: String,
+        archive: bool,
+    },
+    Mark {
+        habit: String,
+        date: Option<NaiveDate>,
+        value: Option<String>,
+    },
+    Show {
+        week: bool,
+    },
+    Stats {
+        habit: String,
+    },
+    Undo,
+    Redo,
+    Backups,
+    Restore {
+        index: usize,
+        yes: bool,
+    },
+    Help,
+}
+
+impl Subcommand {
+    /// Parse the subcommand from the command line arguments, excluding the binary name
+    pub fn parse(args: &[String]) -> Result<Subcommand> {
+        let (name, rest) = match args.split_first() {
+            Some((name, rest)) => (name.as_str(), rest),
+            None => return Ok(Subcommand::Help),
+        };
+        let (positional, flags) = split_flags(rest)?;
+
+        let subcommand = match name {
+            "list" | "ls" => Subcommand::List,
+            "add" => {
+                let habit_type = match flag(&flags, "type") {
+                    Some(t) => HabitType::from_name(t)
+                        .ok_or_else(|| eyre!("Unknown habit type `{}`", t))?,
+                    None => HabitType::BIT,
+                };
+                Subcommand::Add {
+                    label: single(&positional, "add <HABIT_NAME>")?,
+                    habit_type,
+                    schedule: flag(&flags, "schedule").map(|s| s.to_owned()),
+                    goal: flag(&flags, "goal")
+                        .map(parse_goal)
+                        .transpose()
+                        .map_err(|e| eyre!(e))?,
+                    unit: flag(&flags, "unit").map(|s| s.to_owned()),
+                    group: flag(&flags, "group").map(|s| s.to_owned()),
+                }
+            }
+            "goal" => match positional.as_slice() {
+                [habit, goal] => Subcommand::Goal {
+                    habit: habit.to_owned(),
+                    goal: parse_goal(goal).map_err(|e| eyre!(e))?,
+                    unit: flag(&flags, "unit").map(|s| s.to_owned()),
+                },
+                _ => bail!("Usage: rabit goal <HABIT> <GOAL> [--unit UNIT]"),
+            },
+            "schedule" => match positional.as_slice() {
+                [habit, rule] => Subcommand::Schedule {
+                    habit: habit.to_owned(),
+                    rule: rule.to_owned(),
+                },
+                _ => bail!("Usage: rabit schedule <HABIT> <SCHEDULE>"),
+            },
+            "delete" | "rm" => Subcommand::Delete {
+                habit: single(&positional, "delete <HABIT>")?,
+                yes: has_flag(&flags, "yes"),
+            },
+            "archive" | "unarchive" => Subcommand::Archive {
+                habit: single(&positional, &format!("{} <HABIT>", name))?,
+                archive: name == "archive",
+            },
+            "mark" => {
+                let date = match flag(&flags, "date") {
+                    Some(d) => Some(
+                        NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                            .map_err(|_| eyre!("Invalid date `{}`, use YYYY-MM-DD", d))?,
+                    ),
+                    None => None,
+                };
+                Subcommand::Mark {
+                    habit: single(&positional, "mark <HABIT>")?,
+                    date,
+                    value: flag(&flags, "value").map(|v| v.to_owned()),
+                }
+            }
+            "show" => {
+                if !positional.is_empty() {
+                    bail!("`show` takes no arguments");
+                }
+                Subcommand::Show {
+                    week: has_flag(&flags, "week"),
+                }
+            }
+            "stats" => Subcommand::Stats {
+                habit: single(&positional, "stats <HABIT>")?,
+            },
+            "undo" => Subcommand::Undo,
+            "redo" => Subcommand::Redo,
+            "backups" => Subcommand::Backups,
+            "restore" => Subcommand::Restore {
+                index: match positional.as_slice() {
+                    [] => 0,
+                    [i] => i
+                        .parse::<usize>()
+                        .map_err(|_| eyre!("Invalid backup number `{}`", i))?,
+                    _ => bail!("Usage: rabit restore [BACKUP_NUMBER]"),
+                },
+                yes: has_flag(&flags, "yes"),
+            },
+            "help" | "--help" | "-h" => Subcommand::Help,
+            other => bail!("Unknown command `{}`\n\n{}", other, USAGE),
+        };
+
+        // Catch typos in flag names instead of silently ignoring them
+        let allowed: &[&str] = match subcommand {
+            Subcommand::Add { .. } => &["type", "schedule", "goal", "unit", "group"],
+            Subcommand::Goal { .. } => &["unit"],
+            Subcommand::Mark { .. } => &["date", "value"],
+            Subcommand::Show { .. } => &["week"],
+            Subcommand::Delete { .. } | Subcommand::Restore { .. } => &["yes"],
+            _ => &[],
+        };
+        if let Some((unknown, _)) = flags.iter().find(|(f, _)| !allowed.contains(&f.as_str())) {
+            bail!("Unknown flag `--{}` for `{}`", unknown, name);
+        }
+        Ok(subcommand)
+    }
+}
+
+/// `--flag [value]` pairs, in the order they were given
+type Flags = Vec<(String, Option<String>)>;
+
+/// Split arguments into positional arguments and `--flag [value]` pairs
+fn split_flags(args: &[String]) -> Result<(Vec<String>, Flags)> {
+    let mut positional = vec![];
+    let mut flags = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                // `--week` and `--yes` don't take a value
+                let value = if name == "week" || name == "yes" {
+                    None
+                } else {
+                    match iter.next() {
+                        Some(v) => Some(v.to_owned()),
+                        None => bail!("Missing value for `--{}`", name),
+                    }
+                };
+                flags.push((name.to_owned(), value));
+            }
+            None => positional.push(arg.to_owned()),
+        }
+    }
+    Ok((positional, flags))
+}
+
+fn flag<'a>(flags: &'a [(String, Option<String>)], name: &str) -> Option<&'a str> {
+    flags
+        .iter()
+        .find(|(f, _)| f == name)
+        .and_then(|(_, v)| v.as_deref())
+}
+
+fn has_flag(flags: &[(String, Option<String>)], name: &str) -> bool {
+    flags.iter().any(|(f, _)| f == name)
+}
+
+fn single(positional: &[String], usage: &str) -> Result<String> {
+    match positional {
+        [arg] => Ok(arg.to_owned()),
+        _ => bail!("Usage: rabit {}", usage),
+    }
+}
+
+/// Run a subcommand against the tracker stored in the directory and persist the changes,
+/// writing what it prints to out
+pub fn run(dir: &Path, args: &[String], out: &mut impl Write) -> Result<()> {
+    let subcommand = Subcommand::parse(args)?;
+
+    // These don't need the current state, which may be the thing being recovered
+    match subcommand {
+        Subcommand::Help => {
+            writeln!(out, "{}", USAGE)?;
+            return Ok(());
+        }
+        Subcommand::Backups => {
+            for (i, backup) in storage::list_backups(dir)?.iter().enumerate() {
+                if let Some(time) = storage::backup_time(backup) {
+                    writeln!(out, "{:>3}  {}", i, time.format("%Y-%m-%d %H:%M:%S"))?;
+                }
+            }
+            return Ok(());
+        }
+        Subcommand::Restore { index, yes } => {
+            if !yes && !ask(&format!("Replace all habits with backup #{}?", index))? {
+                bail!("Not restoring");
+            }
+            HabitTracker::restore_backup(dir, index)?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let config = Config::load(dir)?;
+    let mut tracker = load_tracker(dir, &config)?;
+    let mut history = History::load(dir, &tracker.habits);
+    match subcommand {
+        Subcommand::List => {
+            for habit in &tracker.habits {
+                let archived = match habit.archived {
+                    Some(day) => format!("  (archived on {})", day),
+                    None => String::new(),
+                };
+                let group = match &habit.group {
+                    Some(group) => format!("  [{}]", group),
+                    None => String::new(),
+                };
+                writeln!(
+                    out,
+                    "{:<16} {:<6} {:<12} {}{}{}",
+                    habit.id,
+                    format!("{:?}", habit.habit_type),
+                    habit.schedule.to_string(),
+                    habit.label,
+                    group,
+                    archived
+                )?;
+            }
+        }
+        Subcommand::Add {
+            label,
+            habit_type,
+            schedule,
+            goal,
+            unit,
+            group,
+        } => {
+            tracker
+                .add_configured_habit(label, habit_type, schedule.as_deref(), goal, unit, group)
+                .map_err(|e| eyre!(e))?;
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Goal { habit, goal, unit } => {
+            let id = find(&tracker, &habit)?;
+            tracker.habits[id].set_goal(goal, unit);
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Schedule { habit, rule } => {
+            let id = find(&tracker, &habit)?;
+            tracker.set_schedule(id, &rule).map_err(|e| eyre!(e))?;
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Delete { habit, yes } => {
+            let id = find(&tracker, &habit)?;
+            let question = format!("Delete `{}` and all its entries?", tracker.habits[id].label);
+            if !yes && !ask(&question)? {
+                bail!("Not deleting");
+            }
+            tracker.habits.remove(id);
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Archive { habit, archive } => {
+            let id = find(&tracker, &habit)?;
+            tracker.set_archived(id, archive).map_err(|e| eyre!(e))?;
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Mark { habit, date, value } => {
+            let id = find(&tracker, &habit)?;
+            let date = date.unwrap_or_else(|| tracker.today());
+            // A date given on the command line is backfilled on purpose, but is never to come
+            Editing::Free
+                .check(date, tracker.today())
+                .map_err(|e| eyre!("Can't mark `{}`: {}", habit, e))?;
+            mark(&mut tracker, id, date, value)?;
+            save(dir, &tracker, &mut history)?;
+        }
+        Subcommand::Show { week } => {
+            // The grid may show any number of days, --week asks for the whole week instead
+            if week {
+                tracker.set_grid(Grid {
+                    days: 7,
+                    ..tracker.grid
+                });
+            }
+            tracker.this_week();
+            write!(out, "{}", render_week(&tracker, &config.glyphs))?;
+        }
+        Subcommand::Stats { habit } => {
+            let habit = &tracker.habits[find(&tracker, &habit)?];
+            let stats = Stats::new(habit, tracker.today(), tracker.grid.week_start);
+            writeln!(out, "Current streak   {}", stats.current_streak)?;
+            writeln!(out, "Longest streak   {}", stats.longest_streak)?;
+            writeln!(
+                out,
+                "Last {} days     {:.0}%",
+                RATE_DAYS,
+                stats.completion_rate * 100.0
+            )?;
+            if matches!(habit.habit_type, HabitType::COUNT | HabitType::NUMBER) {
+                let unit = habit.unit.clone().unwrap_or_default();
+                writeln!(out, "This week        {} {}", stats.week_total, unit)?;
+                writeln!(out, "This month       {} {}", stats.month_total, unit)?;
+            }
+            for (c, n) in stats.histogram {
+                writeln!(out, "{}                {}", c, n)?;
+            }
+        }
+        Subcommand::Undo | Subcommand::Redo => {
+            let (change, verb, done) = match subcommand {
+                Subcommand::Undo => (history.undo(&mut tracker.habits), "undo", "Undid"),
+                _ => (history.redo(&mut tracker.habits), "redo", "Redid"),
+            };
+            let description = change.ok_or_else(|| eyre!("Nothing to {}", verb))?;
+            tracker.store_state(dir)?;
+            history.store(dir)?;
+            writeln!(out, "{} {}", done, description)?;
+        }
+        Subcommand::Help | Subcommand::Backups | Subcommand::Restore { .. } => {}
+    }
+    Ok(())
+}
+
+/// Store the tracker and record the change in the history, so that it can be undone
+fn save(dir: &Path, tracker: &HabitTracker, history: &mut History) -> Result<()> {
+    tracker.store_state(dir)?;
+    if history.record(&tracker.habits) {
+        history.store(dir)?;
+    }
+    Ok(())
+}
+
+/// Load the tracker from the disk. If the state file is corrupt, offer to restore
+/// the latest backup instead of starting over with an empty tracker
+pub fn load_tracker(dir: &Path, config: &Config) -> Result<HabitTracker> {
+    let mut tracker = fetch_or_recover(dir)?;
+    tracker.configure(config);
+    Ok(tracker)
+}
+
+fn fetch_or_recover(dir: &Path) -> Result<HabitTracker> {
+    let err = match HabitTracker::fetch_state(dir) {
+        Ok(tracker) => return Ok(tracker),
+        Err(err) => err,
+    };
+    if !matches!(err.downcast_ref(), Some(LoadError::Corrupt { .. })) {
+        return Err(err);
+    }
+
+    eprintln!("Error: {}", err);
+    let latest = storage::list_backups(dir)?
+        .first()
+        .and_then(|b| storage::backup_time(b));
+    let latest = match latest {
+        Some(latest) => latest,
+        None => bail!("No backups to restore from, fix or remove the file to continue"),
+    };
+    let question = format!(
+        "Restore the latest backup from {}?",
+        latest.format("%Y-%m-%d %H:%M:%S")
+    );
+    if !ask(&question)? {
+        bail!("Not restoring, fix the file or run `rabit restore` to continue");
+    }
+    HabitTracker::restore_backup(dir, 0)
+}
+
+/// Ask a yes or no question on the terminal
+/// Anything but a yes, including no terminal at all, is a no
+fn ask(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn find(tracker: &HabitTracker, query: &str) -> Result<usize> {
+    tracker.find_habit(query).map_err(|e| eyre!(e))
+}
+
+/// Mark a habit for a day. Unlike the UI, marking a BIT habit is idempotent
+/// so that it can safely be run from hooks and cron jobs
+fn mark(
+    tracker: &mut HabitTracker,
+    id: usize,
+    date: NaiveDate,
+    value: Option<String>,
+) -> Result<()> {
+    let habit = &mut tracker.habits[id];
+    match (&habit.habit_type, value) {
+        (HabitType::BIT, None) => habit.set_stat(date, Entry::Done),
+        (HabitType::BIT, Some(_)) => bail!("`{}` is a BIT habit and takes no value", habit.label),
+        // Without a value, counts and amounts go up by one
+        (HabitType::COUNT, None) | (HabitType::NUMBER, None) => habit.check_task(date, None),
+        (HabitType::ALPHA, None) => bail!("`{}` is an ALPHA habit, pass a --value", habit.label),
+        (_, Some(v)) => habit.set_value(date, &v).map_err(|e| eyre!(e))?,
+    }
+    Ok(())
+}
+
+/// Render the week in view as plain text
+fn render_week(tracker: &HabitTracker, glyphs: &Glyphs) -> String {
+    let labels = tracker.labels();
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let dates = tracker.get_date_range();
+    let mut out = format!("{:<width$}  ", "", width = width);
+    for day in &dates {
+        out.push_str(&format!("{:^3}", &day.weekday().to_string()[..2]));
+    }
+    out.push_str(&format!("\n{:<width$}  ", "", width = width));
+    for h in tracker.get_header_labels() {
+        out.push_str(&h);
+    }
+    out.push('\n');
+    let today = tracker.today();
+    // Archived habits are left out
+    let values = tracker.values_v2();
+    for i in tracker.active() {
+        let row = &values[i];
+        out.push_str(&format!("{:<width$}  ", labels[i], width = width));
+        let habit = &tracker.habits[i];
+        let statuses = habit.statuses(
+            dates[0],
+            dates[dates.len() - 1],
+            today,
+            tracker.grid.week_start,
+        );
+        for (j, stat) in row.iter().enumerate() {
+            let not_due = statuses[j] == Status::NotDue;
+            let noted = habit.notes.contains_key(&dates[j]);
+            out.push_str(
+                &glyphs
+                    .of(&habit.habit_type)
+                    .cell(stat.as_ref(), noted, not_due),
+            );
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -1,12 +1,24 @@
 use crate::app::ui;
-use app::{App, AppMode};
+use app::{
+    keymap::{Action, Key},
+    App, AppMode,
+};
 use crossterm::event::{self, Event, KeyCode};
 use eyre::Result;
-use std::{cell::RefCell, io::stdout, rc::Rc};
+use std::{
+    cell::RefCell,
+    io::stdout,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use tui::{backend::CrosstermBackend, Terminal};
 
 pub mod app;
+pub mod cli;
+
+// How often the config file is checked for changes while waiting for keys
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Start the UI of the application
 pub fn start_ui(app: Rc<RefCell<App>>) -> Result<()> {
@@ -18,42 +30,58 @@ pub fn start_ui(app: Rc<RefCell<App>>) -> Result<()> {
     terminal.hide_cursor()?;
 
     // Render Loop
-    loop {
+    let result = loop {
         let mut app = app.borrow_mut();
+        app.reload_config();
+        app.reload_state();
+        app.keymap.expire(Instant::now());
         terminal.draw(|rect| ui::draw(rect, &mut app))?;
 
-        if let Event::Key(key) = event::read()? {
+        if !event::poll(RELOAD_INTERVAL)? {
+            continue;
+        }
+        let event = event::read()?;
+        // The next draw fits the grid to the new size
+        if let Event::Resize(_, _) = event {
+            terminal.autoresize()?;
+        }
+        if let Event::Key(key) = event {
             match app.mode {
-                app::AppMode::NORMAL => match key.code {
-                    KeyCode::Char('q') => {
-                        app.tracker.store_state();
-                        break;
-                    }
-                    KeyCode::Char('k') => app.move_cursor_up(),
-                    KeyCode::Char('j') => app.move_cursor_down(),
-                    KeyCode::Char('h') => app.move_cursor_left(),
-                    KeyCode::Char('l') => app.move_cursor_right(),
-                    KeyCode::Char(' ') => {
-                        // If the habit is of type BIT, then mark it
-                        // Otherwise, enter HABIT mode
-                        app.mark_habit();
-                    }
-                    KeyCode::Char(':') => app.enter_command_mode(),
-                    _ => {}
+                // Any key closes the help
+                app::AppMode::NORMAL if app.show_help => app.show_help = false,
+                app::AppMode::NORMAL => match app.keymap.feed(Key::from(key)) {
+                    // Changes are saved as they are made
+                    Some((Action::Quit, _)) => break Ok(()),
+                    Some((action, count)) => app.run_action(action, count),
+                    None => {}
                 },
                 app::AppMode::COMMAND => match key.code {
                     KeyCode::Esc => {
                         app.input = String::new();
                         app.mode = AppMode::NORMAL;
                     }
-                    KeyCode::Char(c) => app.input.push(c),
                     KeyCode::Enter => {
+                        // The command can ask for a confirmation
+                        app.mode = AppMode::NORMAL;
                         app.execute_input();
-                        app.mode = AppMode::NORMAL
                     }
+                    _ => app.edit_input(Key::from(key)),
+                },
+                app::AppMode::CONFIRM => app.confirm(matches!(key.code, KeyCode::Char('y'))),
+                // COUNT and NUMBER habits take a typed value
+                app::AppMode::HABIT if app.entering_value() => match key.code {
+                    KeyCode::Char(c) if c.is_ascii_digit() || c == '.' => app.input.push(c),
                     KeyCode::Backspace => {
                         app.input.pop();
                     }
+                    KeyCode::Enter => {
+                        app.complete_value_entry();
+                        app.mode = AppMode::NORMAL;
+                    }
+                    KeyCode::Esc => {
+                        app.input = String::new();
+                        app.mode = AppMode::NORMAL;
+                    }
                     _ => {}
                 },
                 app::AppMode::HABIT => match key.code {
@@ -65,10 +93,10 @@ pub fn start_ui(app: Rc<RefCell<App>>) -> Result<()> {
                 },
             }
         }
-    }
+    };
 
     terminal.clear()?;
     terminal.show_cursor()?;
     crossterm::terminal::disable_raw_mode()?;
-    Ok(())
+    result
 }
@@ -1,10 +1,22 @@
 use eyre::Result;
 use std::{cell::RefCell, rc::Rc};
 
-use rabit::{app::App, start_ui};
+use rabit::{
+    app::{config::Config, storage, App},
+    cli, start_ui,
+};
 
 pub fn main() -> Result<()> {
-    let app = Rc::new(RefCell::new(App::new().unwrap()));
+    // Run a subcommand without entering the UI, if one was given
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dir = storage::data_dir()?;
+    if !args.is_empty() {
+        return cli::run(&dir, &args, &mut std::io::stdout());
+    }
+
+    let config = Config::load(&dir)?;
+    let tracker = cli::load_tracker(&dir, &config)?;
+    let app = Rc::new(RefCell::new(App::new(dir, tracker, config)?));
     start_ui(app)?;
     Ok(())
 }
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use rabit::app::config::Config;
+use rabit::app::habit::{Entry, HabitTracker, HabitType};
+use rabit::cli::{self, Subcommand};
+
+mod common;
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(|a| a.to_owned()).collect()
+}
+
+fn parse(line: &str) -> Result<Subcommand, String> {
+    Subcommand::parse(&args(line)).map_err(|e| e.to_string())
+}
+
+/// Run the command line against the directory, returning what it printed
+fn run(dir: &Path, line: &str) -> Result<String, String> {
+    let mut out = vec![];
+    cli::run(dir, &args(line), &mut out).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn stored(dir: &Path) -> HabitTracker {
+    HabitTracker::fetch_state(dir).unwrap()
+}
+
+#[test]
+fn subcommands_are_parsed() {
+    assert_eq!(parse(""), Ok(Subcommand::Help));
+    assert_eq!(parse("ls"), Ok(Subcommand::List));
+    assert_eq!(
+        parse("add Run --type number --goal 5 --unit km --group Sport"),
+        Ok(Subcommand::Add {
+            label: String::from("Run"),
+            habit_type: HabitType::NUMBER,
+            schedule: None,
+            goal: Some(5.0),
+            unit: Some(String::from("km")),
+            group: Some(String::from("Sport")),
+        })
+    );
+    assert_eq!(
+        parse("mark read --date 2026-03-14 --value 3"),
+        Ok(Subcommand::Mark {
+            habit: String::from("read"),
+            date: NaiveDate::from_ymd_opt(2026, 3, 14),
+            value: Some(String::from("3")),
+        })
+    );
+    assert_eq!(parse("show --week"), Ok(Subcommand::Show { week: true }));
+    assert_eq!(parse("show"), Ok(Subcommand::Show { week: false }));
+    assert_eq!(
+        parse("rm read --yes"),
+        Ok(Subcommand::Delete {
+            habit: String::from("read"),
+            yes: true
+        })
+    );
+    assert_eq!(
+        parse("restore 2"),
+        Ok(Subcommand::Restore {
+            index: 2,
+            yes: false
+        })
+    );
+}
+
+#[test]
+fn bad_arguments_are_refused() {
+    let error = |line: &str| parse(line).unwrap_err();
+    assert_eq!(error("show --yes"), "Unknown flag `--yes` for `show`");
+    assert_eq!(error("list --week"), "Unknown flag `--week` for `list`");
+    assert_eq!(
+        error("add Run --tpye count"),
+        "Unknown flag `--tpye` for `add`"
+    );
+    assert_eq!(error("mark read --value"), "Missing value for `--value`");
+    assert!(error("mark read --date 14/03/2026").contains("Invalid date"));
+    assert!(error("add Run --type text").contains("Unknown habit type"));
+    assert!(error("goal run -1").contains("invalid goal"));
+    assert!(error("fly").contains("Unknown command `fly`"));
+    assert!(error("mark").contains("Usage: rabit mark"));
+}
+
+#[test]
+fn marking_a_bit_habit_twice_leaves_it_done() {
+    let dir = common::data_dir("cli-mark-bit");
+    run(&dir, "add Read").unwrap();
+    run(&dir, "mark read --date 2026-03-14").unwrap();
+    run(&dir, "mark read --date 2026-03-14").unwrap();
+    let day = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+    assert_eq!(stored(&dir).habits[0].stats[&day], Entry::Done);
+
+    let error = run(&dir, "mark read --value 3").unwrap_err();
+    assert!(error.contains("takes no value"), "{}", error);
+}
+
+#[test]
+fn alpha_habits_are_marked_with_a_value() {
+    let dir = common::data_dir("cli-mark-alpha");
+    run(&dir, "add Mood --type alpha").unwrap();
+    let error = run(&dir, "mark mood").unwrap_err();
+    assert_eq!(error, "`Mood` is an ALPHA habit, pass a --value");
+    assert!(stored(&dir).habits[0].stats.is_empty());
+
+    run(&dir, "mark mood --date 2026-03-14 --value h").unwrap();
+    let day = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+    assert_eq!(stored(&dir).habits[0].stats[&day], Entry::Char('h'));
+}
+
+#[test]
+fn the_days_of_the_grid_are_shown() {
+    let dir = common::data_dir("cli-show");
+    run(&dir, "add Read").unwrap();
+    run(&dir, "add Walk").unwrap();
+    run(&dir, "archive walk").unwrap();
+    let error = run(&dir, "archive walk").unwrap_err();
+    assert!(error.contains("was already archived on"), "{}", error);
+    run(&dir, "mark read").unwrap();
+
+    let shown = run(&dir, "show").unwrap();
+    let lines: Vec<&str> = shown.lines().collect();
+    // Two lines of days, and one for each habit that isn't archived
+    assert_eq!(lines.len(), 3, "{}", shown);
+    assert_eq!(lines[0].chars().count(), "Read  ".len() + 7 * 3);
+    assert!(lines[2].starts_with("Read  "));
+    let done = Config::default()
+        .glyphs
+        .of(&HabitType::BIT)
+        .cell(Some(&Entry::Done), false, false);
+    assert!(lines[2].contains(&done), "{}", shown);
+
+    // The grid shows 3 days, unless the whole week is asked for
+    fs::write(dir.join("config.toml"), "[grid]\ndays = 3\n").unwrap();
+    let days = |line: &str| {
+        run(&dir, line)
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .chars()
+            .count()
+    };
+    assert_eq!(days("show"), "Read  ".len() + 3 * 3);
+    assert_eq!(days("show --week"), "Read  ".len() + 7 * 3);
+}
@@ -0,0 +1,281 @@
+use chrono::NaiveDate;
+use rabit::app::command::{completions, parse, tokenize, Command};
+use rabit::app::habit::{HabitTracker, HabitType};
+use rabit::app::keymap::Action;
+use rabit::app::{Order, View};
+
+fn words(input: &str) -> Vec<String> {
+    tokenize(input)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.text)
+        .collect()
+}
+
+fn error(input: &str) -> String {
+    parse(input).expect_err(input)
+}
+
+#[test]
+fn words_are_split_on_whitespace_and_quotes() {
+    assert_eq!(words("  add   Read  "), ["add", "Read"]);
+    assert_eq!(
+        words("add 'Read books' \"Go running\""),
+        ["add", "Read books", "Go running"]
+    );
+    assert_eq!(words(r"add Read\ books"), ["add", "Read books"]);
+    assert_eq!(words(r#"note "say \"hi\"""#), ["note", r#"say "hi""#]);
+    assert_eq!(words("note didn't sleep"), ["note", "didn't", "sleep"]);
+    assert_eq!(words("add ''"), ["add", ""]);
+    assert!(words("").is_empty());
+}
+
+#[test]
+fn unclosed_quotes_and_trailing_escapes_are_errors() {
+    assert!(tokenize("add 'Read")
+        .unwrap_err()
+        .contains("unclosed quote"));
+    assert!(tokenize("add \"Read").is_err());
+    assert!(tokenize(r"add Read\").is_err());
+}
+
+#[test]
+fn add_takes_named_flags() {
+    assert_eq!(
+        parse(r#"add "Read" --type count --goal 30"#),
+        Ok(Command::Add {
+            label: String::from("Read"),
+            habit_type: HabitType::COUNT,
+            schedule: None,
+            goal: Some(30.0),
+            unit: None,
+            group: None,
+        })
+    );
+    assert_eq!(
+        parse("add Run --type=number --goal=5 --unit km --schedule 3/week --group Health"),
+        Ok(Command::Add {
+            label: String::from("Run"),
+            habit_type: HabitType::NUMBER,
+            schedule: Some(String::from("3/week")),
+            goal: Some(5.0),
+            unit: Some(String::from("km")),
+            group: Some(String::from("Health")),
+        })
+    );
+}
+
+#[test]
+fn add_keeps_the_positional_type() {
+    let habit_type = |input: &str| match parse(input) {
+        Ok(Command::Add { habit_type, .. }) => habit_type,
+        other => panic!("{} parsed to {:?}", input, other),
+    };
+    assert_eq!(habit_type("add Read"), HabitType::BIT);
+    assert_eq!(habit_type("add Read number"), HabitType::NUMBER);
+    assert_eq!(habit_type("add Read 3"), HabitType::COUNT);
+    assert_eq!(habit_type("add Mood a"), HabitType::ALPHA);
+    assert!(error("add Read nonsense").contains("unknown habit type"));
+    assert!(error("add Read count --type bit").contains("twice"));
+}
+
+#[test]
+fn flags_are_checked() {
+    assert!(error("add Read --colour red").contains("unknown flag `--colour`"));
+    assert!(error("add Read --goal").contains("missing value for `--goal`"));
+    assert!(error("add Read --goal 1 --goal 2").contains("twice"));
+    assert!(error("add Read --goal -1").contains("invalid goal"));
+    assert!(error("delete Read --type count").contains("takes no flags"));
+    // Quoted words are never flags
+    assert_eq!(
+        parse("edit 0 '--loud'"),
+        Ok(Command::Edit {
+            habit: String::from("0"),
+            label: String::from("--loud"),
+        })
+    );
+}
+
+#[test]
+fn habits_are_referred_to_by_id_or_name() {
+    assert_eq!(
+        parse("edit Read 'Read books'"),
+        Ok(Command::Edit {
+            habit: String::from("Read"),
+            label: String::from("Read books"),
+        })
+    );
+    assert_eq!(
+        parse("goal water 8 glasses"),
+        Ok(Command::Goal {
+            habit: String::from("water"),
+            goal: 8.0,
+            unit: Some(String::from("glasses")),
+        })
+    );
+    assert_eq!(
+        parse("goal Water 0 --unit glasses"),
+        Ok(Command::Goal {
+            habit: String::from("Water"),
+            goal: 0.0,
+            unit: Some(String::from("glasses")),
+        })
+    );
+    assert_eq!(
+        parse("schedule 'Read books' mon,wed"),
+        Ok(Command::Schedule {
+            habit: String::from("Read books"),
+            rule: String::from("mon,wed"),
+        })
+    );
+    assert_eq!(
+        parse("delete 2"),
+        Ok(Command::Delete {
+            habit: String::from("2")
+        })
+    );
+}
+
+#[test]
+fn other_commands() {
+    assert_eq!(parse("skip"), Ok(Command::Skip));
+    assert_eq!(
+        parse("note slept  in"),
+        Ok(Command::Note {
+            text: String::from("slept in")
+        })
+    );
+    assert_eq!(parse("restore"), Ok(Command::Restore { index: 0 }));
+    assert_eq!(parse("restore 3"), Ok(Command::Restore { index: 3 }));
+    assert_eq!(
+        parse("view all"),
+        Ok(Command::View(View::Month { all: true }))
+    );
+    assert_eq!(parse("view archive"), Ok(Command::View(View::Archive)));
+    assert_eq!(
+        parse("goto 2026-03-14"),
+        Ok(Command::Goto(NaiveDate::from_ymd(2026, 3, 14)))
+    );
+    assert!(error("goto 2026-02-30").contains("invalid date"));
+    assert_eq!(parse("backfill"), Ok(Command::Backfill));
+    assert_eq!(parse("archive"), Ok(Command::Archive { habit: None }));
+    assert_eq!(
+        parse("unarchive Read"),
+        Ok(Command::Unarchive {
+            habit: Some(String::from("Read"))
+        })
+    );
+    assert!(error("archive Read Run").contains("usage"));
+    assert_eq!(
+        parse("group Read 'Morning routine'"),
+        Ok(Command::Group {
+            habit: String::from("Read"),
+            group: Some(String::from("Morning routine"))
+        })
+    );
+    assert_eq!(
+        parse("group Read"),
+        Ok(Command::Group {
+            habit: String::from("Read"),
+            group: None
+        })
+    );
+    assert_eq!(parse("fold"), Ok(Command::Fold { group: None }));
+    assert_eq!(parse("sort streak"), Ok(Command::Sort(Order::Streak)));
+    assert_eq!(parse("sort manual"), Ok(Command::Sort(Order::Manual)));
+    assert!(error("sort size").contains("usage"));
+    assert_eq!(parse("undo"), Ok(Command::Undo));
+    assert_eq!(parse("map"), Ok(Command::Map { binding: None }));
+    assert_eq!(
+        parse("map <C-n> down"),
+        Ok(Command::Map {
+            binding: Some((String::from("<C-n>"), Action::Down))
+        })
+    );
+    assert!(error("map <C-n> fly").contains("unknown action `fly`"));
+    assert!(error("view decade").contains("usage"));
+    assert!(error("restore latest").contains("invalid backup number"));
+    assert!(error("undo twice").contains("takes no arguments"));
+    assert!(error("fly").contains("unknown command `fly`"));
+}
+
+#[test]
+fn short_and_empty_input_is_an_error() {
+    assert!(error("").contains("no command"));
+    assert!(error("   ").contains("no command"));
+    for command in [
+        "add",
+        "edit",
+        "edit 1",
+        "goal",
+        "goal 1",
+        "schedule",
+        "schedule 1",
+        "delete",
+        "note",
+        "view",
+        "goto",
+    ] {
+        assert!(parse(command).is_err(), "{}", command);
+    }
+}
+
+#[test]
+fn completions_follow_the_grammar() {
+    let habits = vec![
+        String::from("read-books"),
+        String::from("run"),
+        String::from("Read books"),
+        String::from("Run"),
+    ];
+    let complete =
+        |before: &str, word: &str| completions(&tokenize(before).unwrap(), word, &habits);
+    assert!(complete("", "").contains(&String::from("schedule")));
+    assert_eq!(
+        complete("add Read", "--"),
+        ["--type", "--schedule", "--goal", "--unit", "--group"]
+    );
+    assert_eq!(
+        complete("add Read --type", ""),
+        ["bit", "count", "alpha", "number"]
+    );
+    assert_eq!(
+        complete("delete", ""),
+        ["read-books", "run", "Read books", "Run"]
+    );
+    assert_eq!(
+        complete("view", "m"),
+        ["week", "month", "all", "year", "archive"]
+    );
+    assert_eq!(complete("sort", ""), ["manual", "name", "streak", "rate"]);
+    assert!(complete("edit 1", "").is_empty());
+    assert!(complete("fly", "").is_empty());
+}
+
+#[test]
+fn habits_are_found_by_id_name_prefix_or_letters() {
+    let mut tracker = HabitTracker::default();
+    for label in ["Read books", "Run", "Water", "Read papers", "10"] {
+        tracker.add_habit(label.to_owned(), HabitType::BIT);
+    }
+    tracker.habits[2].label = String::from("Drink water");
+    assert_eq!(tracker.habits[2].id, "water");
+
+    assert_eq!(tracker.find_habit("water"), Ok(2));
+    assert_eq!(tracker.find_habit("RUN"), Ok(1));
+    assert_eq!(tracker.find_habit("10"), Ok(4));
+    // Numbers are names like any other, not places in the list
+    assert!(tracker.find_habit("3").is_err());
+    assert_eq!(tracker.find_habit("read-p"), Ok(3));
+    assert_eq!(tracker.find_habit("dri"), Ok(2));
+    assert_eq!(tracker.find_habit("rdpap"), Ok(3));
+    assert!(tracker
+        .find_habit("read")
+        .unwrap_err()
+        .contains("Read books, Read papers"));
+    assert!(tracker
+        .find_habit("5")
+        .unwrap_err()
+        .contains("no habit matches"));
+    assert!(tracker.find_habit("").is_err());
+}
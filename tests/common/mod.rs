@@ -0,0 +1,11 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// An empty data directory for one test, so that tests never read or write the user's
+/// habits, history or config
+pub fn data_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rabit-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,148 @@
+use std::fs;
+
+use chrono::{Duration, NaiveDate, Weekday};
+use rabit::app::config::{self, Config, Grid};
+use rabit::app::habit::{HabitTracker, HabitType};
+use rabit::app::keymap::Action;
+use rabit::app::{App, View};
+
+mod common;
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn tracker(days: usize, week_start: Weekday) -> HabitTracker {
+    let mut tracker = HabitTracker::default();
+    tracker.set_grid(Grid { days, week_start });
+    tracker
+}
+
+#[test]
+fn weeks_start_on_the_configured_day() {
+    let mut monday = tracker(7, Weekday::Mon);
+    assert_eq!(monday.show_day(day("2026-03-14")), 5);
+    assert_eq!(monday.start_date, day("2026-03-09"));
+
+    let mut sunday = tracker(7, Weekday::Sun);
+    assert_eq!(sunday.show_day(day("2026-03-14")), 6);
+    assert_eq!(sunday.start_date, day("2026-03-08"));
+    assert_eq!(sunday.show_day(day("2026-03-15")), 0);
+    sunday.next_week();
+    assert_eq!(sunday.get_date_range()[0], day("2026-03-22"));
+}
+
+#[test]
+fn weeks_run_across_new_year() {
+    let mut monday = tracker(7, Weekday::Mon);
+    // 2026 has an ISO week 53, which ends in 2027
+    assert_eq!(monday.show_day(day("2027-01-01")), 4);
+    assert_eq!(monday.start_date, day("2026-12-28"));
+    assert_eq!(monday.show_day(day("2027-01-03")), 6);
+    assert_eq!(monday.show_day(day("2021-01-03")), 6);
+    assert_eq!(monday.start_date, day("2020-12-28"));
+    monday.next_week();
+    assert_eq!(monday.start_date, day("2021-01-04"));
+    monday.previous_week();
+    monday.previous_week();
+    assert_eq!(monday.start_date, day("2020-12-21"));
+    // The first of January of 2024 is a Monday
+    assert_eq!(monday.show_day(day("2024-01-01")), 0);
+    assert_eq!(monday.show_day(day("2023-12-31")), 6);
+
+    let mut sunday = tracker(7, Weekday::Sun);
+    assert_eq!(sunday.show_day(day("2027-01-01")), 5);
+    assert_eq!(sunday.start_date, day("2026-12-27"));
+}
+
+#[test]
+fn goto_and_today_select_the_day() {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), HabitType::BIT);
+    tracker.add_habit(String::from("Run"), HabitType::BIT);
+    let dir = common::data_dir("goto");
+    let mut app = App::new(dir, tracker, Config::default()).unwrap();
+    let selected = |app: &App| {
+        let (row, col) = app.state.selected().unwrap();
+        (row, app.tracker.get_date_range()[col])
+    };
+
+    app.input = String::from("goto 2027-01-01");
+    app.execute_input();
+    assert_eq!(selected(&app), (0, day("2027-01-01")));
+    app.run_action(Action::Down, None);
+    app.run_action(Action::PreviousPage, None);
+    assert_eq!(selected(&app), (1, day("2026-12-25")));
+    assert_eq!(app.tracker.start_date, day("2026-12-21"));
+
+    app.input = String::from("view month");
+    app.execute_input();
+    app.input = String::from("goto 2026-12-31");
+    app.execute_input();
+    app.run_action(Action::NextPage, None);
+    assert_eq!(selected(&app), (1, day("2027-01-31")));
+
+    app.input = String::from("view archive");
+    app.execute_input();
+    app.run_action(Action::Today, None);
+    assert_eq!(app.view, View::Week);
+    assert_eq!(selected(&app).1, app.tracker.today());
+
+    // Changes are saved to the directory of the test, without the days in view
+    app.run_action(Action::Mark, None);
+    let stored = fs::read_to_string(app.dir.join("habit.json")).unwrap();
+    assert!(stored.contains(&app.tracker.today().to_string()));
+    assert!(!stored.contains("start_date"));
+    assert!(app.dir.join("command_history.json").exists());
+}
+
+#[test]
+fn other_numbers_of_days_end_on_today() {
+    let mut tracker = tracker(14, Weekday::Sun);
+    let today = tracker.today();
+    assert_eq!(tracker.show_day(today), 13);
+    assert_eq!(tracker.get_date_range().len(), 14);
+    assert_eq!(tracker.show_day(today - Duration::days(13)), 0);
+    assert_eq!(tracker.show_day(today - Duration::days(14)), 13);
+    tracker.next_week();
+    assert_eq!(tracker.get_date_range()[13], today);
+}
+
+#[test]
+fn the_grid_is_configured_by_days_and_week_start() {
+    let config: Config = toml::from_str("[grid]\ndays = 30\nweek_start = \"sunday\"").unwrap();
+    assert_eq!(
+        config.grid,
+        Grid {
+            days: 30,
+            week_start: Weekday::Sun
+        }
+    );
+    assert_eq!(Config::default().grid.days, 7);
+    assert!(toml::from_str::<Config>("[grid]\ndays = 0").is_err());
+    assert!(toml::from_str::<Config>("[grid]\nweek_start = \"someday\"").is_err());
+}
+
+#[test]
+fn the_view_opens_on_today_in_the_configured_timezone() {
+    let data = fs::read_to_string("tests/fixtures/v10.json").unwrap();
+    // A day apart or more at any time
+    for zone in ["Pacific/Kiritimati", "Pacific/Pago_Pago"] {
+        let config = Config {
+            timezone: Some(zone.parse().unwrap()),
+            grid: Grid {
+                days: 1,
+                week_start: Weekday::Mon,
+            },
+            ..Config::default()
+        };
+        let (mut tracker, _) = HabitTracker::from_json(&data).unwrap();
+        tracker.configure(&config);
+        assert_eq!(
+            tracker.get_date_range(),
+            [config::today(config.timezone)],
+            "{}",
+            zone
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::fs;
+
+use chrono::{Duration, NaiveDate};
+use rabit::app::config::{Config, Editing};
+use rabit::app::habit::{Entry, HabitTracker, HabitType};
+use rabit::app::keymap::Action;
+use rabit::app::App;
+
+mod common;
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn app(test: &str, editing: Editing) -> App {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), HabitType::BIT);
+    let config = Config {
+        editing,
+        ..Config::default()
+    };
+    let mut app = App::new(common::data_dir(test), tracker, config).unwrap();
+    app.run_action(Action::Today, None);
+    app
+}
+
+#[test]
+fn the_policy_decides_which_past_days_can_be_changed() {
+    let today = day("2026-03-14");
+    assert!(Editing::Today.check(today, today).is_ok());
+    assert!(Editing::Today
+        .check(day("2026-03-13"), today)
+        .unwrap_err()
+        .contains("only today"));
+    assert!(Editing::Past(2).check(day("2026-03-12"), today).is_ok());
+    assert!(Editing::Past(2).check(day("2026-03-11"), today).is_err());
+    assert!(Editing::Free.check(day("2020-01-01"), today).is_ok());
+    for editing in [Editing::Today, Editing::Past(2), Editing::Free] {
+        assert!(editing
+            .check(day("2026-03-15"), today)
+            .unwrap_err()
+            .contains("yet to come"));
+    }
+}
+
+#[test]
+fn the_policy_is_configured_by_name_or_days() {
+    let editing = |toml: &str| toml::from_str::<Config>(toml).map(|c| c.editing);
+    assert_eq!(Config::default().editing, Editing::Today);
+    assert_eq!(editing("editing = \"free\""), Ok(Editing::Free));
+    assert_eq!(editing("editing = \"today\""), Ok(Editing::Today));
+    assert_eq!(editing("editing = 3"), Ok(Editing::Past(3)));
+    assert!(editing("editing = \"sometimes\"").is_err());
+    assert!(editing("editing = -1").is_err());
+}
+
+#[test]
+fn past_days_are_locked_until_backfilling() {
+    let mut app = app("backfill", Editing::Today);
+    let today = app.tracker.today();
+    let yesterday = today - Duration::days(1);
+    assert!(app.can_edit(today).is_ok());
+
+    app.input = format!("goto {}", yesterday);
+    app.execute_input();
+    app.run_action(Action::Mark, None);
+    assert!(app.input.starts_with("Error! only today"));
+    assert!(app.tracker.habits[0].stats.is_empty());
+
+    app.input = String::from("backfill");
+    app.execute_input();
+    assert!(app.backfill);
+    assert!(app.can_edit(yesterday).is_ok());
+    assert!(app.can_edit(today + Duration::days(1)).is_err());
+    // Backfilled days are saved to the directory of the test
+    app.run_action(Action::Mark, None);
+    assert_eq!(app.tracker.habits[0].stats[&yesterday], Entry::Done);
+    let stored = fs::read_to_string(app.dir.join("habit.json")).unwrap();
+    assert!(stored.contains(&yesterday.to_string()));
+
+    app.input = String::from("backfill");
+    app.execute_input();
+    assert!(app.can_edit(yesterday).is_err());
+    app.run_action(Action::Mark, None);
+    assert_eq!(app.tracker.habits[0].stats[&yesterday], Entry::Done);
+}
+
+#[test]
+fn days_to_come_are_never_changed() {
+    let mut app = app("future", Editing::Free);
+    let tomorrow = app.tracker.today() + Duration::days(1);
+    app.input = format!("goto {}", tomorrow);
+    app.execute_input();
+    app.run_action(Action::Mark, None);
+    app.input = String::from("note later");
+    app.execute_input();
+    assert!(app.input.contains("yet to come"));
+    assert!(app.tracker.habits[0].stats.is_empty());
+    assert!(!app.dir.join("habit.json").exists());
+}
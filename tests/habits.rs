@@ -0,0 +1,183 @@
+use chrono::NaiveDate;
+use rabit::app::habit::{compact, Entry, Habit, HabitTracker, HabitType};
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn habit(habit_type: HabitType) -> Habit {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), habit_type);
+    tracker.habits.remove(0)
+}
+
+#[test]
+fn marking_and_noting_the_same_day_keeps_both() {
+    let today = day("2026-03-14");
+    let mut read = habit(HabitType::BIT);
+    read.check_task(today, None);
+    read.set_note(today, String::from("chapter 3"));
+    assert_eq!(read.stats[&today], Entry::Done);
+    assert_eq!(read.notes[&today], "chapter 3");
+
+    // Unmarking or skipping the day keeps the note too
+    read.check_task(today, None);
+    assert!(read.stats.is_empty());
+    read.toggle_skip(today);
+    assert_eq!(read.stats[&today], Entry::Skipped);
+    assert_eq!(read.notes[&today], "chapter 3");
+
+    let mut water = habit(HabitType::COUNT);
+    water.set_note(today, String::from("hot day"));
+    water.check_task(today, None);
+    water.set_value(today, "5").unwrap();
+    assert_eq!(water.stats[&today], Entry::Count(5));
+    assert_eq!(water.notes[&today], "hot day");
+}
+
+#[test]
+fn numbers_fit_in_a_cell() {
+    let cases = [
+        (0.0, "0"),
+        (2.0, "2"),
+        (2.5, "2.5"),
+        (9.94, "9.9"),
+        (9.96, "10"),
+        (12.4, "12"),
+        (999.4, "999"),
+        (999.6, "1k"),
+        (1500.0, "2k"),
+        (98_700.0, "99k"),
+        (250_000.0, "99k"),
+    ];
+    for (x, text) in cases {
+        assert_eq!(compact(x), text, "{}", x);
+        assert!(compact(x).chars().count() <= 3, "{}", x);
+    }
+}
+
+#[test]
+fn values_are_checked_for_the_type() {
+    let today = day("2026-03-14");
+    let mut water = habit(HabitType::COUNT);
+    water.set_value(today, " 8 ").unwrap();
+    assert_eq!(water.stats[&today], Entry::Count(8));
+    for bad in ["-1", "1.5", "many", ""] {
+        assert!(water.set_value(today, bad).is_err(), "{}", bad);
+    }
+    assert_eq!(water.stats[&today], Entry::Count(8));
+    water.set_value(today, "0").unwrap();
+    assert!(water.stats.is_empty());
+
+    let mut run = habit(HabitType::NUMBER);
+    run.set_value(today, "5.2").unwrap();
+    assert_eq!(run.stats[&today], Entry::Amount(5.2));
+    for bad in ["-1", "NaN", "inf", "-0.5", "km"] {
+        assert!(run.set_value(today, bad).is_err(), "{}", bad);
+    }
+    assert_eq!(run.stats[&today], Entry::Amount(5.2));
+    run.set_value(today, "0.0").unwrap();
+    assert!(run.stats.is_empty());
+
+    let mut mood = habit(HabitType::ALPHA);
+    mood.set_value(today, "é").unwrap();
+    assert_eq!(mood.stats[&today], Entry::Char('é'));
+    assert!(mood.set_value(today, "ok").is_err());
+    assert!(habit(HabitType::BIT).set_value(today, "1").is_err());
+}
+
+#[test]
+fn decrementing_removes_the_entry_at_zero() {
+    let today = day("2026-03-14");
+    let mut water = habit(HabitType::COUNT);
+    water.set_value(today, "2").unwrap();
+    water.decrement(today);
+    assert_eq!(water.stats[&today], Entry::Count(1));
+    water.decrement(today);
+    assert!(water.stats.is_empty());
+    water.decrement(today);
+    assert!(water.stats.is_empty());
+
+    let mut run = habit(HabitType::NUMBER);
+    run.set_value(today, "1.5").unwrap();
+    run.decrement(today);
+    assert_eq!(run.stats[&today], Entry::Amount(0.5));
+    run.decrement(today);
+    assert!(run.stats.is_empty());
+
+    // Other entries are left alone
+    let mut read = habit(HabitType::BIT);
+    read.check_task(today, None);
+    read.decrement(today);
+    assert_eq!(read.stats[&today], Entry::Done);
+}
+
+#[test]
+fn progress_is_measured_against_the_goal() {
+    let today = day("2026-03-14");
+    let mut water = habit(HabitType::COUNT);
+    water.set_value(today, "4").unwrap();
+    assert_eq!(water.progress(today), None);
+    water.goal = Some(0.0);
+    assert_eq!(water.progress(today), None);
+
+    water.goal = Some(8.0);
+    assert_eq!(water.progress(today), Some(0.5));
+    assert_eq!(water.progress(day("2026-03-13")), None);
+    water.set_value(today, "12").unwrap();
+    assert_eq!(water.progress(today), Some(1.0));
+    assert!(water.stats[&today].is_done(water.goal));
+}
+
+#[test]
+fn habits_are_added_and_changed_with_their_settings() {
+    let mut tracker = HabitTracker::default();
+    let error = tracker.add_configured_habit(
+        String::from("Run"),
+        HabitType::NUMBER,
+        Some("3/year"),
+        Some(5.0),
+        None,
+        None,
+    );
+    assert!(error.is_err());
+    assert!(
+        tracker.habits.is_empty(),
+        "nothing is added with a bad schedule"
+    );
+
+    tracker
+        .add_configured_habit(
+            String::from("Run"),
+            HabitType::NUMBER,
+            Some("3/week"),
+            Some(0.0),
+            Some(String::from("km")),
+            Some(String::new()),
+        )
+        .unwrap();
+    let run = &tracker.habits[0];
+    assert_eq!(
+        (run.goal, run.unit.as_deref(), run.group.as_deref()),
+        (None, Some("km"), None)
+    );
+
+    // The unit stays unless a new one is given
+    tracker.habits[0].set_goal(5.0, None);
+    assert_eq!(tracker.habits[0].goal, Some(5.0));
+    assert_eq!(tracker.habits[0].unit.as_deref(), Some("km"));
+    assert!(tracker.set_schedule(0, "someday").is_err());
+    tracker.set_schedule(0, "daily").unwrap();
+
+    assert_eq!(
+        tracker.set_archived(0, false),
+        Err(String::from("`Run` isn't archived"))
+    );
+    tracker.set_archived(0, true).unwrap();
+    let error = tracker.set_archived(0, true).unwrap_err();
+    assert!(
+        error.starts_with("`Run` was already archived on "),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,133 @@
+use std::fs;
+
+use chrono::NaiveDate;
+use rabit::app::habit::{Entry, HabitTracker, HabitType};
+use rabit::app::history::{History, MAX_CHANGES};
+
+mod common;
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn tracker() -> HabitTracker {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), HabitType::BIT);
+    tracker.add_habit(String::from("Water"), HabitType::COUNT);
+    tracker
+}
+
+#[test]
+fn changes_are_undone_and_redone() {
+    let mut tracker = tracker();
+    let mut history = History::new(&tracker.habits);
+    let before = tracker.habits.clone();
+
+    tracker.habits[0].check_task(day("2026-03-14"), None);
+    assert!(history.record(&tracker.habits));
+    assert!(!history.record(&tracker.habits));
+    tracker.add_habit(String::from("Walk"), HabitType::BIT);
+    tracker.habits.swap(0, 2);
+    history.record(&tracker.habits);
+    tracker.habits[1].archived = Some(day("2026-03-15"));
+    tracker.habits[1].set_note(day("2026-03-15"), String::from("thirsty"));
+    history.record(&tracker.habits);
+    tracker.habits.remove(2);
+    history.record(&tracker.habits);
+    let after = tracker.habits.clone();
+
+    assert_eq!(
+        history.undo(&mut tracker.habits).as_deref(),
+        Some("deleting `Read`")
+    );
+    assert_eq!(
+        history.undo(&mut tracker.habits).as_deref(),
+        Some("archiving `Water`")
+    );
+    assert_eq!(
+        history.undo(&mut tracker.habits).as_deref(),
+        Some("adding `Walk`")
+    );
+    assert_eq!(
+        history.undo(&mut tracker.habits).as_deref(),
+        Some("marking `Read` on 2026-03-14")
+    );
+    assert_eq!(tracker.habits, before);
+    assert_eq!(history.undo(&mut tracker.habits), None);
+
+    while history.redo(&mut tracker.habits).is_some() {}
+    assert_eq!(tracker.habits, after);
+}
+
+#[test]
+fn a_new_change_clears_what_could_be_redone() {
+    let mut tracker = tracker();
+    let mut history = History::new(&tracker.habits);
+    tracker.habits[0].check_task(day("2026-03-14"), None);
+    history.record(&tracker.habits);
+    history.undo(&mut tracker.habits);
+
+    tracker.habits[1].check_task(day("2026-03-14"), None);
+    history.record(&tracker.habits);
+    assert_eq!(history.redo(&mut tracker.habits), None);
+    assert!(tracker.habits[0].stats.is_empty());
+    assert_eq!(tracker.habits[1].stats[&day("2026-03-14")], Entry::Count(1));
+}
+
+#[test]
+fn only_the_latest_changes_are_kept() {
+    let mut tracker = tracker();
+    let mut history = History::new(&tracker.habits);
+    let first = day("2026-01-01");
+    for days in 0..=MAX_CHANGES as i64 {
+        tracker.habits[0].check_task(first + chrono::Duration::days(days), None);
+        history.record(&tracker.habits);
+    }
+
+    let mut undone = 0;
+    while history.undo(&mut tracker.habits).is_some() {
+        undone += 1;
+    }
+    assert_eq!(undone, MAX_CHANGES);
+    // The first change is gone for good
+    assert_eq!(tracker.habits[0].stats.keys().collect::<Vec<_>>(), [&first]);
+}
+
+#[test]
+fn only_what_changed_is_stored() {
+    let dir = common::data_dir("history-store");
+    let mut tracker = tracker();
+    for days in 0..365 {
+        tracker.habits[0].check_task(day("2025-01-01") + chrono::Duration::days(days), None);
+    }
+    let mut history = History::new(&tracker.habits);
+    tracker.habits[1].check_task(day("2026-03-14"), None);
+    history.record(&tracker.habits);
+    history.store(&dir).unwrap();
+
+    let stored = fs::read_to_string(dir.join("history.json")).unwrap();
+    assert!(stored.contains("2026-03-14"));
+    assert!(!stored.contains("2025-"));
+
+    // It is loaded again for the same habits, and can still be undone
+    let mut loaded = History::load(&dir, &tracker.habits);
+    assert_eq!(loaded, history);
+    assert!(loaded.undo(&mut tracker.habits).is_some());
+    assert!(tracker.habits[1].stats.is_empty());
+}
+
+#[test]
+fn a_history_of_other_habits_is_discarded() {
+    let dir = common::data_dir("history-discard");
+    let mut tracker = tracker();
+    let mut history = History::new(&tracker.habits);
+    tracker.habits[0].check_task(day("2026-03-14"), None);
+    history.record(&tracker.habits);
+    history.store(&dir).unwrap();
+
+    // The habits were changed without the history, like by another copy of rabit
+    tracker.habits[1].check_task(day("2026-03-14"), None);
+    let mut loaded = History::load(&dir, &tracker.habits);
+    assert_eq!(loaded, History::new(&tracker.habits));
+    assert_eq!(loaded.undo(&mut tracker.habits), None);
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crossterm::event::KeyCode;
+use rabit::app::config::Config;
+use rabit::app::habit::{HabitTracker, HabitType};
+use rabit::app::keymap::{parse_sequence, Action, Key, KeyMap, Sequences, SEQUENCE_TIMEOUT};
+use rabit::app::App;
+
+mod common;
+
+fn key(c: char) -> Key {
+    Key {
+        code: KeyCode::Char(c),
+        ctrl: false,
+    }
+}
+
+fn keymap() -> KeyMap {
+    KeyMap::new(&BTreeMap::new()).unwrap()
+}
+
+/// Feed every character of the keys, returning the action of the last one
+fn feed(keymap: &mut KeyMap, keys: &str) -> Option<(Action, Option<usize>)> {
+    keys.chars().map(|c| keymap.feed(key(c))).last().flatten()
+}
+
+#[test]
+fn sequences_are_parsed() {
+    assert_eq!(parse_sequence("gg"), Ok(vec![key('g'), key('g')]));
+    assert_eq!(
+        parse_sequence("<C-d>"),
+        Ok(vec![Key {
+            code: KeyCode::Char('d'),
+            ctrl: true
+        }])
+    );
+    assert_eq!(parse_sequence("<space>"), Ok(vec![key(' ')]));
+    assert_eq!(parse_sequence("<lt>a"), Ok(vec![key('<'), key('a')]));
+    assert_eq!(parse_sequence("<"), Ok(vec![key('<')]));
+    assert_eq!(
+        parse_sequence("z<BS>"),
+        Ok(vec![
+            key('z'),
+            Key {
+                code: KeyCode::Backspace,
+                ctrl: false
+            }
+        ])
+    );
+    // Key names are only read inside `<…>`
+    assert_eq!(parse_sequence("bs"), Ok(vec![key('b'), key('s')]));
+    assert_eq!(parse_sequence("end").unwrap().len(), 3);
+    assert_eq!(parse_sequence("é"), Ok(vec![key('é')]));
+
+    assert!(parse_sequence("<nope>")
+        .unwrap_err()
+        .contains("unknown key"));
+    assert!(parse_sequence("").unwrap_err().contains("empty"));
+}
+
+#[test]
+fn counts_are_typed_before_the_sequence() {
+    let mut keymap = keymap();
+    assert_eq!(feed(&mut keymap, "j"), Some((Action::Down, None)));
+    assert_eq!(feed(&mut keymap, "5"), None);
+    assert_eq!(keymap.pending(), "5");
+    assert_eq!(feed(&mut keymap, "j"), Some((Action::Down, Some(5))));
+    assert_eq!(
+        feed(&mut keymap, "10gg"),
+        Some((Action::FirstHabit, Some(10)))
+    );
+    assert_eq!(feed(&mut keymap, "99999k"), Some((Action::Up, Some(9999))));
+
+    // A 0 is a key of its own unless it continues a count
+    assert_eq!(feed(&mut keymap, "0"), Some((Action::FirstDay, None)));
+
+    // Esc and keys that don't go on to any sequence drop the count
+    assert_eq!(feed(&mut keymap, "3g"), None);
+    assert_eq!(keymap.pending(), "3g");
+    keymap.feed(Key {
+        code: KeyCode::Esc,
+        ctrl: false,
+    });
+    assert_eq!(keymap.pending(), "");
+    assert_eq!(feed(&mut keymap, "3gx"), None);
+    assert_eq!(feed(&mut keymap, "j"), Some((Action::Down, None)));
+}
+
+#[test]
+fn unfinished_sequences_time_out() {
+    let mut keymap = keymap();
+    let start = Instant::now();
+    feed(&mut keymap, "2g");
+    assert!(!keymap.expire(start));
+    assert_eq!(keymap.pending(), "2g");
+    assert!(keymap.expire(Instant::now() + SEQUENCE_TIMEOUT));
+    assert_eq!(keymap.pending(), "");
+    // The next `g` starts over
+    assert_eq!(feed(&mut keymap, "g"), None);
+    assert_eq!(feed(&mut keymap, "g"), Some((Action::FirstHabit, None)));
+    assert!(!keymap.expire(Instant::now() + SEQUENCE_TIMEOUT));
+}
+
+#[test]
+fn clashing_sequences_are_refused() {
+    let config = |action: &str, sequences: &[&str]| {
+        let sequences = sequences.iter().map(|s| s.to_string()).collect();
+        BTreeMap::from([(action.to_owned(), Sequences::Many(sequences))])
+    };
+
+    // `g` would run before `gg` could be typed
+    let error = KeyMap::new(&config("today", &["g"])).unwrap_err();
+    assert!(error.contains("`gg` for first_habit clashes with `g` for today"));
+    let error = KeyMap::new(&config("quit", &["q", "j"])).unwrap_err();
+    assert!(error.contains("bound to both"), "{}", error);
+    assert!(KeyMap::new(&config("fly", &["f"]))
+        .unwrap_err()
+        .contains("unknown action"));
+
+    // Bound actions lose their defaults, so their keys can be taken
+    let mut keymap = KeyMap::new(&config("first_habit", &["<Home>"])).unwrap();
+    keymap.bind("g", Action::Today).unwrap();
+    assert_eq!(feed(&mut keymap, "g"), Some((Action::Today, None)));
+    assert!(keymap.bind("gx", Action::Help).is_err());
+    // Binding again for the session replaces the action
+    keymap.bind("g", Action::Help).unwrap();
+    assert_eq!(feed(&mut keymap, "g"), Some((Action::Help, None)));
+}
+
+#[test]
+fn counts_go_to_the_row_numbered_from_one() {
+    let mut tracker = HabitTracker::default();
+    for label in ["Read", "Run", "Walk", "Water"] {
+        tracker.add_habit(label.to_owned(), HabitType::BIT);
+    }
+    let mut app = App::new(common::data_dir("counts"), tracker, Config::default()).unwrap();
+    let mut go = |keys: &str| {
+        let mut keymap = keymap();
+        let (action, count) = feed(&mut keymap, keys).unwrap();
+        app.run_action(action, count);
+        app.state.selected().map(|(row, _)| row)
+    };
+    assert_eq!(go("G"), Some(3));
+    assert_eq!(go("1G"), Some(0));
+    assert_eq!(go("3gg"), Some(2));
+    assert_eq!(go("gg"), Some(0));
+    assert_eq!(go("2G"), Some(1));
+    // Counts past the last row stop on it
+    assert_eq!(go("9gg"), Some(3));
+}
@@ -0,0 +1,93 @@
+use chrono::Weekday;
+use rabit::app::config::{Config, Grid};
+use rabit::app::habit::{HabitTracker, HabitType};
+use rabit::app::ui::{self, fit};
+use rabit::app::{App, AppState, View};
+use tui::{backend::TestBackend, layout::Rect, Terminal};
+
+mod common;
+
+fn app(habits: usize) -> App {
+    let mut tracker = HabitTracker::default();
+    for i in 0..habits {
+        let label = format!("Habit with a rather long name {}", i);
+        tracker.add_habit(label, HabitType::COUNT);
+    }
+    tracker.habits[0].group = Some(String::from("Morning"));
+    tracker.habits[1].archived = Some(tracker.today());
+    App::new(common::data_dir("layout"), tracker, Config::default()).unwrap()
+}
+
+#[test]
+fn every_view_draws_at_the_edge_sizes() {
+    // Nothing at all, just under the height of the title, header and bars, just under the
+    // width of one day of the grid, just under the width of the heatmap, and plenty of room
+    let sizes = [(0, 0), (120, 6), (14, 40), (64, 40), (120, 40)];
+    let mut app = app(12);
+    app.run_action(rabit::app::keymap::Action::LastHabit, None);
+    for (days, week_start) in [(7, Weekday::Mon), (3, Weekday::Sun), (30, Weekday::Sun)] {
+        app.tracker.set_grid(Grid { days, week_start });
+        app.run_action(rabit::app::keymap::Action::LastDay, None);
+        for view in [
+            View::Week,
+            View::Month { all: false },
+            View::Year,
+            View::Archive,
+        ] {
+            app.view = view;
+            for (width, height) in sizes {
+                let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+                terminal.draw(|f| ui::draw(f, &mut app)).unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn the_grid_gives_way_to_small_terminals() {
+    let size = |width, height| Rect::new(0, 0, width, height);
+    let full = fit(size(120, 40), View::Week, 12, 7, 20).unwrap();
+    assert!(full.stats);
+    assert_eq!((full.label_width, full.days, full.rows), (20, 7, 12));
+
+    // The stats panel goes first, then the margins and the days
+    let narrow = fit(size(50, 40), View::Week, 12, 7, 20).unwrap();
+    assert!(!narrow.stats);
+    assert_eq!((narrow.margin, narrow.label_width, narrow.days), (4, 20, 7));
+    let narrower = fit(size(30, 40), View::Week, 12, 7, 20).unwrap();
+    assert_eq!(
+        (narrower.margin, narrower.label_width, narrower.days),
+        (1, 10, 6)
+    );
+
+    // Lines that don't fit are scrolled to
+    let short = fit(size(120, 12), View::Week, 12, 7, 20).unwrap();
+    assert_eq!((short.vertical_margin, short.rows), (0, 5));
+
+    // More days than fit are scrolled to as well
+    let month = fit(size(80, 40), View::Week, 12, 30, 20).unwrap();
+    assert_eq!(
+        (month.stats, month.label_width, month.days),
+        (false, 10, 22)
+    );
+
+    assert_eq!(fit(size(14, 40), View::Week, 12, 7, 20), None);
+    assert!(fit(size(15, 40), View::Week, 12, 7, 20).is_some());
+    assert_eq!(fit(size(120, 7), View::Week, 12, 7, 20), None);
+    assert!(fit(size(120, 7), View::Week, 0, 7, 0).is_some());
+}
+
+#[test]
+fn the_grid_scrolls_as_little_as_needed() {
+    let mut state = AppState::default();
+    assert_eq!(state.page(), None);
+    assert_eq!(state.scroll_to(Some(3), 10, 40), 0);
+    assert_eq!(state.page(), Some(10));
+    assert_eq!(state.scroll_to(Some(15), 10, 40), 6);
+    assert_eq!(state.scroll_to(Some(10), 10, 40), 6);
+    assert_eq!(state.scroll_to(Some(2), 10, 40), 2);
+    assert_eq!(state.scroll_to(Some(39), 10, 40), 30);
+    // Lines that are gone leave no space at the bottom
+    assert_eq!(state.scroll_to(None, 10, 25), 15);
+    assert_eq!(state.scroll_to(None, 30, 25), 0);
+}
@@ -0,0 +1,248 @@
+use chrono::{NaiveDate, Weekday};
+use rabit::app::config::Config;
+use rabit::app::habit::{Entry, HabitTracker, HabitType};
+use rabit::app::migration::{FormatError, SCHEMA_VERSION};
+use rabit::app::schedule::Schedule;
+
+// Every format rabit has ever written, oldest first
+const FIXTURES: [(&str, &str); 12] = [
+    ("v0_done_dates", include_str!("fixtures/v0_done_dates.json")),
+    ("v0_stats", include_str!("fixtures/v0_stats.json")),
+    ("v1", include_str!("fixtures/v1.json")),
+    ("v2", include_str!("fixtures/v2.json")),
+    ("v3", include_str!("fixtures/v3.json")),
+    ("v4", include_str!("fixtures/v4.json")),
+    ("v5", include_str!("fixtures/v5.json")),
+    ("v6", include_str!("fixtures/v6.json")),
+    ("v7", include_str!("fixtures/v7.json")),
+    ("v8", include_str!("fixtures/v8.json")),
+    ("v9", include_str!("fixtures/v9.json")),
+    ("v10", include_str!("fixtures/v10.json")),
+];
+
+fn load(data: &str) -> (HabitTracker, u32) {
+    HabitTracker::from_json(data).unwrap()
+}
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn every_fixture_loads_into_the_current_schema() {
+    for (name, data) in FIXTURES {
+        let (tracker, _) = HabitTracker::from_json(data)
+            .unwrap_or_else(|e| panic!("fixture {} failed to load: {:?}", name, e));
+        assert_eq!(tracker.schema_version, SCHEMA_VERSION, "fixture {}", name);
+        assert!(!tracker.habits.is_empty(), "fixture {}", name);
+    }
+}
+
+#[test]
+fn migrated_state_round_trips() {
+    for (name, data) in FIXTURES {
+        let (tracker, _) = load(data);
+        let (reloaded, version) = load(&serde_json::to_string(&tracker).unwrap());
+        assert_eq!(version, SCHEMA_VERSION, "fixture {}", name);
+        assert_eq!(reloaded, tracker, "fixture {}", name);
+    }
+}
+
+#[test]
+fn v0_done_dates_become_bit_stats() {
+    let (tracker, version) = load(include_str!("fixtures/v0_done_dates.json"));
+    assert_eq!(version, 0);
+
+    let read = &tracker.habits[0];
+    assert_eq!(read.habit_type, HabitType::BIT);
+    assert_eq!(read.stats.len(), 2);
+    assert_eq!(read.stats[&day("2022-07-11")], Entry::Done);
+    assert_eq!(read.stats[&day("2022-07-13")], Entry::Done);
+    assert!(tracker.habits[1].stats.is_empty());
+}
+
+#[test]
+fn v0_done_dates_merge_with_existing_stats() {
+    let (tracker, version) = load(include_str!("fixtures/v0_stats.json"));
+    assert_eq!(version, 0);
+
+    let read = &tracker.habits[0];
+    assert_eq!(read.stats.len(), 2);
+    assert_eq!(read.stats[&day("2022-07-11")], Entry::Done);
+    assert_eq!(read.stats[&day("2022-07-12")], Entry::Done);
+
+    let pushups = &tracker.habits[1];
+    assert_eq!(pushups.habit_type, HabitType::COUNT);
+    assert_eq!(pushups.stats[&day("2022-07-12")], Entry::Count(3));
+
+    // Done dates have no meaningful ALPHA value and are dropped
+    let mood = &tracker.habits[2];
+    assert_eq!(mood.stats.len(), 1);
+    assert_eq!(mood.stats[&day("2022-07-13")], Entry::Char('h'));
+}
+
+#[test]
+fn v1_stats_become_typed_entries_by_day() {
+    let (tracker, version) = load(include_str!("fixtures/v1.json"));
+    assert_eq!(version, 1);
+
+    let read = &tracker.habits[0];
+    assert_eq!(read.stats.len(), 1);
+    assert_eq!(read.stats[&day("2022-07-11")], Entry::Done);
+    assert_eq!(tracker.habits[1].stats[&day("2022-07-12")], Entry::Count(3));
+    assert_eq!(
+        tracker.habits[2].stats[&day("2022-07-13")],
+        Entry::Char('h')
+    );
+}
+
+#[test]
+fn v1_stats_that_cannot_be_understood_are_dropped() {
+    let data = r#"{"schema_version":1,"start_date":"2022-07-11T00:00:00Z","habits":[
+        {"label":"Pushups","stats":{"2022-07-11 00:00:00 UTC":"many","garbage":"3","2022-07-12 00:00:00 UTC":"4"},"habit_type":"COUNT"},
+        {"label":"Mood","stats":{"2022-07-11 00:00:00 UTC":"ok"},"habit_type":"ALPHA"}]}"#;
+    let (tracker, _) = load(data);
+
+    let pushups = &tracker.habits[0];
+    assert_eq!(pushups.stats.len(), 1);
+    assert_eq!(pushups.stats[&day("2022-07-12")], Entry::Count(4));
+    assert!(tracker.habits[1].stats.is_empty());
+}
+
+#[test]
+fn the_week_in_view_is_not_stored() {
+    let this_week = HabitTracker::default().start_date;
+    for (name, data) in FIXTURES {
+        let (mut tracker, _) = load(data);
+        // The stored week is ignored, the view opens on today once the config is applied
+        tracker.configure(&Config::default());
+        assert_eq!(tracker.start_date, this_week, "fixture {}", name);
+        let stored = serde_json::to_value(&tracker).unwrap();
+        assert!(stored.get("start_date").is_none(), "fixture {}", name);
+    }
+}
+
+#[test]
+fn v3_habits_become_daily() {
+    let (tracker, version) = load(include_str!("fixtures/v3.json"));
+    assert_eq!(version, 3);
+    for habit in tracker.habits {
+        assert_eq!(habit.schedule, Schedule::Daily);
+    }
+}
+
+#[test]
+fn v4_habits_get_no_goal() {
+    let (tracker, version) = load(include_str!("fixtures/v4.json"));
+    assert_eq!(version, 4);
+    assert_eq!(tracker.habits[1].schedule, Schedule::PerWeek(3));
+    assert_eq!(
+        tracker.habits[2].schedule,
+        Schedule::Weekdays(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri])
+    );
+    for habit in tracker.habits {
+        assert_eq!(habit.goal, None);
+        assert_eq!(habit.unit, None);
+    }
+}
+
+#[test]
+fn v5_habits_get_ids_from_their_names() {
+    let (tracker, version) = load(include_str!("fixtures/v5.json"));
+    assert_eq!(version, 5);
+    let ids: Vec<&str> = tracker.habits.iter().map(|h| h.id.as_str()).collect();
+    assert_eq!(ids, ["read", "water", "run"]);
+
+    let data = r#"{"schema_version":5,"start_date":"2022-07-11","habits":[
+        {"label":"Read books","stats":{},"habit_type":"BIT","schedule":"Daily","goal":null,"unit":null},
+        {"label":"read  BOOKS!","stats":{},"habit_type":"BIT","schedule":"Daily","goal":null,"unit":null},
+        {"label":"10","stats":{},"habit_type":"BIT","schedule":"Daily","goal":null,"unit":null},
+        {"label":"☕","stats":{},"habit_type":"BIT","schedule":"Daily","goal":null,"unit":null}]}"#;
+    let (tracker, _) = load(data);
+    let ids: Vec<&str> = tracker.habits.iter().map(|h| h.id.as_str()).collect();
+    assert_eq!(ids, ["read-books", "read-books-2", "habit-10", "habit"]);
+}
+
+#[test]
+fn v6_habits_are_not_archived() {
+    let (tracker, version) = load(include_str!("fixtures/v6.json"));
+    assert_eq!(version, 6);
+    assert_eq!(tracker.active(), [0, 1, 2]);
+    assert!(tracker.archived().is_empty());
+}
+
+#[test]
+fn v7_habits_get_no_group() {
+    let (tracker, version) = load(include_str!("fixtures/v7.json"));
+    assert_eq!(version, 7);
+    for habit in tracker.habits {
+        assert_eq!(habit.group, None);
+    }
+}
+
+#[test]
+fn v8_start_date_is_dropped() {
+    let (tracker, version) = load(include_str!("fixtures/v8.json"));
+    assert_eq!(version, 8);
+    assert_eq!(tracker, load(include_str!("fixtures/v9.json")).0);
+}
+
+#[test]
+fn v9_notes_move_next_to_the_entries() {
+    let (tracker, version) = load(include_str!("fixtures/v4.json"));
+    assert_eq!(version, 4);
+    let mood = &tracker.habits[2];
+    assert_eq!(mood.notes[&day("2022-07-14")], "rough day");
+    assert!(!mood.stats.contains_key(&day("2022-07-14")));
+    assert_eq!(mood.stats[&day("2022-07-13")], Entry::Char('h'));
+
+    let (tracker, version) = load(include_str!("fixtures/v9.json"));
+    assert_eq!(version, 9);
+    assert!(tracker.habits.iter().all(|habit| habit.notes.is_empty()));
+}
+
+#[test]
+fn current_schema_is_not_migrated() {
+    let (tracker, version) = load(include_str!("fixtures/v10.json"));
+    assert_eq!(version, SCHEMA_VERSION);
+    assert_eq!(tracker.habits[0].group.as_deref(), Some("Morning"));
+    assert_eq!(tracker.habits[1].group, None);
+    assert_eq!(tracker.habits[2].archived, Some(day("2022-07-14")));
+    assert_eq!(tracker.active(), [0, 1]);
+    assert_eq!(tracker.archived(), [2]);
+    // IDs stay the same when habits are renamed
+    assert_eq!(tracker.habits[0].id, "read");
+    assert_eq!(tracker.habits[0].label, "Read books");
+    assert_eq!(tracker.habits[0].stats[&day("2022-07-12")], Entry::Skipped);
+    // A note is left next to the entry of the day
+    assert_eq!(tracker.habits[0].stats[&day("2022-07-11")], Entry::Done);
+    assert_eq!(tracker.habits[0].notes[&day("2022-07-11")], "chapter 3");
+    assert_eq!(tracker.habits[1].goal, Some(8.0));
+    assert_eq!(tracker.habits[1].unit.as_deref(), Some("glasses"));
+    assert_eq!(tracker.habits[2].habit_type, HabitType::NUMBER);
+    assert_eq!(
+        tracker.habits[2].stats[&day("2022-07-13")],
+        Entry::Amount(5.2)
+    );
+    assert_eq!(tracker.habits[2].progress(day("2022-07-13")), Some(1.0));
+}
+
+#[test]
+fn newer_schema_is_rejected() {
+    let data = format!(
+        r#"{{"schema_version":{},"start_date":"2022-07-11","habits":[]}}"#,
+        SCHEMA_VERSION + 1
+    );
+    match HabitTracker::from_json(&data) {
+        Err(FormatError::Unsupported(v)) => assert_eq!(v, SCHEMA_VERSION as u64 + 1),
+        other => panic!("expected an unsupported version, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_json_is_rejected() {
+    assert!(matches!(
+        HabitTracker::from_json(r#"{"start_date": "#),
+        Err(FormatError::Invalid(_))
+    ));
+}
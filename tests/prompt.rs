@@ -0,0 +1,65 @@
+use crossterm::event::KeyCode;
+use rabit::app::keymap::Key;
+use rabit::app::prompt::Prompt;
+
+mod common;
+
+fn key(code: KeyCode) -> Key {
+    Key { code, ctrl: false }
+}
+
+#[test]
+fn commands_are_kept_in_the_data_directory() {
+    let dir = common::data_dir("prompt-history");
+    let mut prompt = Prompt::load(&dir);
+    prompt.push(&dir, "view year").unwrap();
+    prompt.push(&dir, "undo").unwrap();
+
+    let mut prompt = Prompt::load(&dir);
+    let mut input = String::new();
+    prompt.edit(&mut input, key(KeyCode::Up), &[]);
+    assert_eq!(input, "undo");
+    prompt.edit(&mut input, key(KeyCode::Up), &[]);
+    assert_eq!(input, "view year");
+}
+
+// Type the text, then the keys, and return the input with the cursor marked by `|`
+fn edit(text: &str, keys: &[Key]) -> String {
+    let mut prompt = Prompt::default();
+    let mut input = String::new();
+    for c in text.chars() {
+        prompt.edit(&mut input, key(KeyCode::Char(c)), &[]);
+    }
+    for &key in keys {
+        prompt.edit(&mut input, key, &[]);
+    }
+    input.insert(prompt.cursor(), '|');
+    input
+}
+
+fn ctrl(c: char) -> Key {
+    Key {
+        code: KeyCode::Char(c),
+        ctrl: true,
+    }
+}
+
+#[test]
+fn non_ascii_input_is_edited_by_characters() {
+    let (left, right) = (key(KeyCode::Left), key(KeyCode::Right));
+    assert_eq!(edit("note café", &[left, left]), "note ca|fé");
+    assert_eq!(edit("note café", &[left, right]), "note café|");
+    assert_eq!(edit("note café", &[key(KeyCode::Backspace)]), "note caf|");
+    assert_eq!(
+        edit("note 日本", &[left, key(KeyCode::Backspace)]),
+        "note |本"
+    );
+    // Option+Space types a non-breaking space on macOS
+    assert_eq!(
+        edit("note\u{a0}très bien", &[ctrl('w')]),
+        "note\u{a0}très |"
+    );
+    assert_eq!(edit("note\u{a0}très", &[ctrl('w')]), "note\u{a0}|");
+    assert_eq!(edit("note\u{a0}très", &[ctrl('w'), ctrl('w')]), "|");
+    assert_eq!(edit("note été", &[left, ctrl('u')]), "|é");
+}
@@ -0,0 +1,217 @@
+use chrono::{Duration, NaiveDate, Weekday};
+use rabit::app::habit::{Habit, HabitTracker, HabitType};
+use rabit::app::schedule::{Schedule, Status};
+use rabit::app::stats;
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn habit(schedule: Schedule) -> Habit {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Run"), HabitType::BIT);
+    let mut habit = tracker.habits.remove(0);
+    habit.schedule = schedule;
+    habit
+}
+
+#[test]
+fn schedules_are_parsed() {
+    let today = day("2026-03-14");
+    assert_eq!(Schedule::parse("daily", today), Ok(Schedule::Daily));
+    assert_eq!(
+        Schedule::parse("fri,mon, Wed,mon", today),
+        Ok(Schedule::Weekdays(vec![
+            Weekday::Mon,
+            Weekday::Wed,
+            Weekday::Fri
+        ]))
+    );
+    assert_eq!(
+        Schedule::parse("every:3", today),
+        Ok(Schedule::EveryNDays {
+            every: 3,
+            from: today
+        })
+    );
+    assert_eq!(Schedule::parse("3/week", today), Ok(Schedule::PerWeek(3)));
+    assert_eq!(
+        Schedule::parse("10/Month", today),
+        Ok(Schedule::PerMonth(10))
+    );
+    // They are written back the way they are typed
+    for rule in ["daily", "mon,wed,fri", "every:3", "3/week", "10/month"] {
+        assert_eq!(Schedule::parse(rule, today).unwrap().to_string(), rule);
+    }
+}
+
+#[test]
+fn bad_schedules_are_refused() {
+    let today = day("2026-03-14");
+    let error = |rule: &str| Schedule::parse(rule, today).unwrap_err();
+    assert!(error("every:0").contains("not a number of days"));
+    assert!(error("every:x").contains("not a number of days"));
+    assert!(error("0/week").contains("not a number of times"));
+    assert!(error("3/year").contains("should be `week` or `month`"));
+    assert!(error("mon,someday").contains("not a schedule"));
+    assert!(error("").contains("not a schedule"));
+}
+
+#[test]
+fn days_off_the_schedule_are_not_due() {
+    let today = day("2026-03-11");
+    let run = habit(Schedule::Weekdays(vec![Weekday::Mon, Weekday::Wed]));
+    // Monday, Tuesday, Wednesday and Thursday
+    assert_eq!(
+        run.status(day("2026-03-09"), today, Weekday::Mon),
+        Status::Missed
+    );
+    assert_eq!(
+        run.status(day("2026-03-10"), today, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(run.status(today, today, Weekday::Mon), Status::Pending);
+    assert_eq!(
+        run.status(day("2026-03-12"), today, Weekday::Mon),
+        Status::NotDue
+    );
+
+    let every = habit(Schedule::EveryNDays {
+        every: 3,
+        from: day("2026-03-01"),
+    });
+    assert_eq!(
+        every.status(day("2026-02-26"), today, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(
+        every.status(day("2026-03-07"), today, Weekday::Mon),
+        Status::Missed
+    );
+    assert_eq!(
+        every.status(day("2026-03-08"), today, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(
+        every.status(day("2026-03-10"), today, Weekday::Mon),
+        Status::Missed
+    );
+}
+
+#[test]
+fn quotas_are_missed_once_their_period_is_over() {
+    let mut run = habit(Schedule::PerWeek(2));
+    // The week of Monday 2026-03-02 ends on Sunday 2026-03-08
+    run.check_task(day("2026-03-03"), None);
+    let sunday = day("2026-03-08");
+    assert_eq!(
+        run.status(day("2026-03-02"), sunday, Weekday::Mon),
+        Status::Pending
+    );
+    assert_eq!(run.status(sunday, sunday, Weekday::Mon), Status::Pending);
+    let monday = day("2026-03-09");
+    assert_eq!(
+        run.status(day("2026-03-02"), monday, Weekday::Mon),
+        Status::Missed
+    );
+    assert_eq!(run.status(sunday, monday, Weekday::Mon), Status::Missed);
+    assert_eq!(
+        run.status(day("2026-03-03"), monday, Weekday::Mon),
+        Status::Done
+    );
+
+    // Once the quota is met the rest of the period is not due
+    run.check_task(day("2026-03-05"), None);
+    assert_eq!(
+        run.status(day("2026-03-02"), monday, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(run.status(sunday, monday, Weekday::Mon), Status::NotDue);
+    assert_eq!(run.status(monday, monday, Weekday::Mon), Status::Pending);
+
+    let mut swim = habit(Schedule::PerMonth(1));
+    swim.check_task(day("2026-02-28"), None);
+    let march = day("2026-03-01");
+    assert_eq!(
+        swim.status(day("2026-02-01"), march, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(swim.status(march, march, Weekday::Mon), Status::Pending);
+    assert_eq!(
+        swim.status(day("2026-03-31"), march, Weekday::Mon),
+        Status::Pending
+    );
+}
+
+#[test]
+fn weekly_quotas_are_counted_in_the_configured_weeks() {
+    let mut run = habit(Schedule::PerWeek(1));
+    // Sunday 2026-03-08 ends a week starting on Monday, and starts one starting on Sunday
+    run.check_task(day("2026-03-08"), None);
+    let today = day("2026-03-10");
+    assert_eq!(
+        run.status(day("2026-03-02"), today, Weekday::Mon),
+        Status::NotDue
+    );
+    assert_eq!(run.status(today, today, Weekday::Mon), Status::Pending);
+    assert_eq!(
+        run.status(day("2026-03-02"), today, Weekday::Sun),
+        Status::Missed
+    );
+    assert_eq!(run.status(today, today, Weekday::Sun), Status::NotDue);
+    assert_eq!(
+        Schedule::PerWeek(1).period(today, Weekday::Sun),
+        Some((day("2026-03-08"), day("2026-03-14")))
+    );
+}
+
+#[test]
+fn statuses_match_the_status_of_every_day() {
+    let mut run = habit(Schedule::PerWeek(3));
+    for date in ["2026-02-23", "2026-02-25", "2026-03-03", "2026-03-10"] {
+        run.check_task(day(date), None);
+    }
+    let (from, to, today) = (day("2026-02-20"), day("2026-03-15"), day("2026-03-11"));
+    let statuses = run.statuses(from, to, today, Weekday::Mon);
+    assert_eq!(statuses.len(), 24);
+    for (i, status) in statuses.into_iter().enumerate() {
+        let date = from + Duration::days(i as i64);
+        assert_eq!(status, run.status(date, today, Weekday::Mon), "{}", date);
+    }
+}
+
+#[test]
+fn streaks_pass_over_days_that_are_not_due() {
+    let mut run = habit(Schedule::Weekdays(vec![
+        Weekday::Mon,
+        Weekday::Wed,
+        Weekday::Fri,
+    ]));
+    for date in ["2026-03-02", "2026-03-04", "2026-03-06", "2026-03-09"] {
+        run.check_task(day(date), None);
+    }
+    // Tuesday 2026-03-10 is not due, Wednesday is today
+    let today = day("2026-03-11");
+    assert_eq!(stats::current_streak(&run, today, Weekday::Mon), 4);
+    assert_eq!(
+        stats::completion_rate(&run, day("2026-03-02"), today, today, Weekday::Mon),
+        1.0
+    );
+
+    let mut walk = habit(Schedule::EveryNDays {
+        every: 2,
+        from: day("2026-03-01"),
+    });
+    for date in ["2026-03-01", "2026-03-03", "2026-03-07"] {
+        walk.check_task(day(date), None);
+    }
+    // 2026-03-05 was missed
+    assert_eq!(
+        stats::current_streak(&walk, day("2026-03-08"), Weekday::Mon),
+        1
+    );
+    assert_eq!(
+        stats::longest_streak(&walk, day("2026-03-08"), Weekday::Mon),
+        2
+    );
+}
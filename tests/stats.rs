@@ -0,0 +1,126 @@
+use chrono::{Duration, NaiveDate, Weekday};
+use rabit::app::habit::{Habit, HabitTracker, HabitType};
+use rabit::app::stats::{self, Stats};
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn habit(habit_type: HabitType) -> Habit {
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), habit_type);
+    tracker.habits.remove(0)
+}
+
+/// Mark the habit done on every day from the first, both included
+fn mark(habit: &mut Habit, from: &str, to: &str) {
+    let (mut date, to) = (day(from), day(to));
+    while date <= to {
+        habit.check_task(date, None);
+        date += Duration::days(1);
+    }
+}
+
+#[test]
+fn the_current_streak_ends_at_the_last_missed_day() {
+    let mut read = habit(HabitType::BIT);
+    mark(&mut read, "2026-03-01", "2026-03-05");
+    mark(&mut read, "2026-03-07", "2026-03-09");
+    let today = day("2026-03-09");
+
+    assert_eq!(stats::current_streak(&read, today, Weekday::Mon), 3);
+    assert_eq!(stats::longest_streak(&read, today, Weekday::Mon), 5);
+
+    // Days missed after the last entry end the streak too
+    let later = day("2026-03-11");
+    assert_eq!(stats::current_streak(&read, later, Weekday::Mon), 0);
+    assert_eq!(stats::longest_streak(&read, later, Weekday::Mon), 5);
+}
+
+#[test]
+fn the_streak_goes_on_through_today_while_it_is_pending() {
+    let mut read = habit(HabitType::BIT);
+    mark(&mut read, "2026-03-01", "2026-03-08");
+    let today = day("2026-03-09");
+    assert_eq!(stats::current_streak(&read, today, Weekday::Mon), 8);
+
+    read.check_task(today, None);
+    assert_eq!(stats::current_streak(&read, today, Weekday::Mon), 9);
+    assert_eq!(stats::longest_streak(&read, today, Weekday::Mon), 9);
+
+    // Skipped days are passed over, neither breaking nor adding to the streak
+    read.toggle_skip(day("2026-03-10"));
+    assert_eq!(
+        stats::current_streak(&read, day("2026-03-10"), Weekday::Mon),
+        9
+    );
+}
+
+#[test]
+fn the_completion_rate_counts_the_due_days_that_are_over() {
+    let mut read = habit(HabitType::BIT);
+    mark(&mut read, "2026-03-01", "2026-03-03");
+    read.toggle_skip(day("2026-03-04"));
+    // 2026-03-05 and 2026-03-06 are missed, 2026-03-07 is today and still pending
+    let today = day("2026-03-07");
+
+    let rate = stats::completion_rate(&read, day("2026-03-01"), today, today, Weekday::Mon);
+    assert!((rate - 3.0 / 5.0).abs() < 1e-9, "{}", rate);
+    assert_eq!(
+        stats::completion_rate(
+            &read,
+            day("2026-03-01"),
+            day("2026-03-03"),
+            today,
+            Weekday::Mon
+        ),
+        1.0
+    );
+    assert_eq!(
+        stats::completion_rate(&read, today, today, today, Weekday::Mon),
+        0.0
+    );
+}
+
+#[test]
+fn an_empty_habit_has_no_stats() {
+    let read = habit(HabitType::COUNT);
+    let today = day("2026-03-09");
+    assert_eq!(
+        Stats::new(&read, today, Weekday::Mon),
+        Stats {
+            current_streak: 0,
+            longest_streak: 0,
+            completion_rate: 0.0,
+            week_total: 0.0,
+            month_total: 0.0,
+            histogram: vec![],
+        }
+    );
+    assert!(stats::weekly_totals(&read, Weekday::Mon).is_empty());
+    assert!(stats::intensity(&read, day("2026-03-01"), today).is_empty());
+}
+
+#[test]
+fn counts_are_summed_by_week_and_month() {
+    let mut water = habit(HabitType::COUNT);
+    water.set_value(day("2026-02-28"), "4").unwrap();
+    water.set_value(day("2026-03-02"), "3").unwrap();
+    water.set_value(day("2026-03-04"), "5").unwrap();
+    let today = day("2026-03-04");
+
+    let summary = Stats::new(&water, today, Weekday::Mon);
+    assert_eq!(summary.week_total, 8.0);
+    assert_eq!(summary.month_total, 8.0);
+    assert_eq!(
+        stats::weekly_totals(&water, Weekday::Mon)[&day("2026-02-23")],
+        4.0
+    );
+    // Weeks starting on Saturday take in 2026-02-28
+    assert_eq!(Stats::new(&water, today, Weekday::Sat).week_total, 12.0);
+    assert_eq!(
+        stats::weekly_totals(&water, Weekday::Sat)[&day("2026-02-28")],
+        12.0
+    );
+    assert_eq!(stats::monthly_totals(&water)[&day("2026-03-01")], 8.0);
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use rabit::app::config::Config;
+use rabit::app::habit::{Entry, HabitTracker, HabitType};
+use rabit::app::storage::{self, backup_state, list_backups, write_atomic, LoadError, MAX_BACKUPS};
+use rabit::app::App;
+
+mod common;
+
+#[test]
+fn files_are_replaced_whole() {
+    let dir = common::data_dir("atomic");
+    let path = dir.join("habit.json");
+    write_atomic(&path, b"old").unwrap();
+    write_atomic(&path, b"new").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    // Nothing is left next to it
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["habit.json"]);
+}
+
+#[test]
+fn only_the_latest_backups_are_kept() {
+    let dir = common::data_dir("backups");
+    // Nothing to back up yet
+    backup_state(&dir, true).unwrap();
+    assert!(list_backups(&dir).unwrap().is_empty());
+
+    let state = storage::state_path(&dir);
+    for i in 0..MAX_BACKUPS + 3 {
+        fs::write(&state, i.to_string()).unwrap();
+        backup_state(&dir, true).unwrap();
+        // Backups are named by the millisecond
+        thread::sleep(Duration::from_millis(2));
+    }
+    let backups = list_backups(&dir).unwrap();
+    assert_eq!(backups.len(), MAX_BACKUPS);
+    let newest = fs::read_to_string(&backups[0]).unwrap();
+    let oldest = fs::read_to_string(&backups[MAX_BACKUPS - 1]).unwrap();
+    assert_eq!(newest, (MAX_BACKUPS + 2).to_string());
+    assert_eq!(oldest, "3");
+
+    // Saving again so soon doesn't rotate the backups
+    fs::write(&state, "latest").unwrap();
+    backup_state(&dir, false).unwrap();
+    assert_eq!(list_backups(&dir).unwrap(), backups);
+}
+
+#[test]
+fn corrupt_state_is_put_aside_once_and_never_overwritten() {
+    let dir = common::data_dir("corrupt");
+    let corrupt = include_str!("fixtures/corrupt.json");
+    let state = storage::state_path(&dir);
+    fs::write(&state, corrupt).unwrap();
+
+    // Loading it again, like when relaunching, doesn't copy it again
+    for _ in 0..3 {
+        let err = HabitTracker::fetch_state(&dir).unwrap_err();
+        match err.downcast_ref() {
+            Some(LoadError::Corrupt {
+                quarantine: Some(copy),
+                ..
+            }) => assert_eq!(fs::read_to_string(copy).unwrap(), corrupt),
+            _ => panic!("expected a corrupt state, got {:?}", err),
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    let copies = fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_str().unwrap().starts_with("habit.corrupt-")
+        })
+        .count();
+    assert_eq!(copies, 1);
+
+    assert!(HabitTracker::default().store_state(&dir).is_err());
+    assert_eq!(fs::read_to_string(&state).unwrap(), corrupt);
+}
+
+#[test]
+fn changes_made_elsewhere_are_kept_when_saving() {
+    let dir = common::data_dir("elsewhere");
+    let mut tracker = HabitTracker::default();
+    tracker.add_habit(String::from("Read"), HabitType::BIT);
+    tracker.add_habit(String::from("Water"), HabitType::COUNT);
+    let mut app = App::new(dir.clone(), tracker, Config::default()).unwrap();
+    app.save();
+    let today = app.tracker.today();
+
+    // Like `rabit mark water` from cron while the grid is open
+    let mut other = HabitTracker::fetch_state(&dir).unwrap();
+    other.habits[1].check_task(today, None);
+    other.store_state(&dir).unwrap();
+
+    app.tracker.habits[0].check_task(today, None);
+    app.save();
+    let stored = HabitTracker::fetch_state(&dir).unwrap();
+    assert_eq!(stored.habits[0].stats[&today], Entry::Done);
+    assert_eq!(stored.habits[1].stats[&today], Entry::Count(1));
+    assert_eq!(app.tracker.habits, stored.habits);
+
+    // Undoing only takes back the change made in the grid
+    app.undo();
+    let stored = HabitTracker::fetch_state(&dir).unwrap();
+    assert!(stored.habits[0].stats.is_empty());
+    assert_eq!(stored.habits[1].stats[&today], Entry::Count(1));
+
+    // Changes are taken in while waiting for keys too
+    let mut other = HabitTracker::fetch_state(&dir).unwrap();
+    other.habits[1].set_note(today, String::from("hot day"));
+    other.store_state(&dir).unwrap();
+    app.reload_state();
+    assert_eq!(app.tracker.habits[1].notes[&today], "hot day");
+}